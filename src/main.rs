@@ -1,77 +1,146 @@
 use std::{
-    fs::read_to_string,
-    io::{stdin, stdout, Write},
+    fs::File,
+    io::{stdin, stdout, BufReader, Write},
 };
 
 use riolox::{
-    parser::Parser, printers::AstPrinter, scanner::Scanner, CompilationError, CompilerResult,
+    coded_error_at, error_at, interpreter::Interpreter, options::LanguageOptions, parser::Parser,
+    resolver::Resolver, scanner::Scanner, CompilationError, CompilerResult,
 };
 
 mod riolox;
 
 fn main() {
-    let mut args = std::env::args();
-    args.next().unwrap();
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let mut options = LanguageOptions::default();
+
+    if let Some(i) = args.iter().position(|arg| arg.starts_with("--dialect")) {
+        let flag = args.remove(i);
+        let dialect = match flag.strip_prefix("--dialect=") {
+            Some(dialect) => Some(dialect.to_owned()),
+            None if i < args.len() => Some(args.remove(i)),
+            None => None,
+        };
+
+        match dialect.as_deref().and_then(LanguageOptions::from_dialect) {
+            Some(dialect) => options = dialect,
+            None => usage(),
+        }
+    }
 
-    if args.len() > 2 {
-        println!("Usage: jlox [script]");
-        std::process::exit(64)
+    if args.len() > 1 {
+        usage();
     }
 
-    if args.len() == 2 {
-        args.next().unwrap();
-        let file = args.next().unwrap();
-        run_file(file);
-    } else {
-        run_prompt();
+    match args.pop() {
+        Some(file) => run_file(file, options),
+        None => run_prompt(options),
     }
 }
 
-fn run_file(file: String) {
-    let content = read_to_string(file).expect("Failed to read file");
-    run(content);
+fn usage() -> ! {
+    println!("Usage: jlox [--dialect strict|extended] [script]");
+    std::process::exit(64)
+}
+
+// The file is scanned as the parser reads it, instead of being read in whole
+// first.
+fn run_file(file: String, options: LanguageOptions) {
+    let input = File::open(file).expect("Failed to read file");
+    let scanner = Scanner::from_reader(BufReader::new(input)).with_options(options);
+    if let Err(error) = try_run(scanner, options, &mut Interpreter::new()) {
+        report(&error);
+        match error {
+            CompilationError::Runtime(_) => std::process::exit(70),
+            _ => std::process::exit(65),
+        }
+    }
 }
 
-fn run_prompt() {
+fn run_prompt(options: LanguageOptions) {
     let stdin = stdin();
     let mut stdout = stdout();
+    let mut interpreter = Interpreter::new();
 
     loop {
         stdout.write_all(b"> ").unwrap();
         stdout.flush().unwrap();
 
         let mut buffer = "".to_owned();
-        let line = stdin.read_line(&mut buffer);
-
-        if line.is_ok() {
-            let _ = try_run(buffer);
-        } else {
+        if !matches!(stdin.read_line(&mut buffer), Ok(n) if n > 0) {
             break;
         }
-    }
-}
 
-fn run(source: String) {
-    let result = try_run(source);
-    if result.is_err() {
-        std::process::exit(65)
+        while needs_more_input(&buffer, options) {
+            stdout.write_all(b". ").unwrap();
+            stdout.flush().unwrap();
+
+            if !matches!(stdin.read_line(&mut buffer), Ok(n) if n > 0) {
+                break;
+            }
+        }
+
+        let scanner = Scanner::new(buffer).with_options(options);
+        if let Err(error) = try_run(scanner, options, &mut interpreter) {
+            report(&error);
+        }
     }
 }
 
-fn try_run(source: String) -> CompilerResult {
-    let mut scanner = Scanner::new(source);
-    let tokens = scanner.scan_tokens();
-    // TODO: Remove clone
-    let mut parser = Parser::new(tokens.to_vec());
+// The line so far ends inside a string, comment or bracket, so ask for a
+// continuation line instead of reporting it as broken.
+fn needs_more_input(source: &str, options: LanguageOptions) -> bool {
+    let mut scanner = Scanner::new(source.to_owned()).with_options(options);
+    scanner.scan_tokens();
+    scanner.needs_more_input()
+}
 
-    let expression = parser.parse();
+// The parser pulls tokens from the scanner as it goes. Scan errors are only
+// known once it is done, and are reported instead of the parse errors they
+// lead to.
+fn try_run(
+    mut scanner: Scanner,
+    options: LanguageOptions,
+    interpreter: &mut Interpreter,
+) -> CompilerResult {
+    let mut parser = Parser::from_tokens(scanner.by_ref()).with_options(options);
+    let program = parser.parse_program();
+    let errors = parser.into_errors();
+
+    if scanner.had_error() {
+        return Err(CompilationError::Scan(scanner.errors().to_vec()));
+    }
+    if !errors.is_empty() {
+        return Err(CompilationError::Parse(errors));
+    }
 
-    if expression.is_none() {
-        return Err(CompilationError::UndefinedError);
+    // The resolver's errors are reported like the parser's, and stop the
+    // program the same way.
+    let errors = Resolver::new(interpreter).resolve(&program);
+    if !errors.is_empty() {
+        return Err(CompilationError::Parse(errors));
     }
 
-    let printer = AstPrinter {};
-    println!("{}", printer.print(&expression.unwrap()));
+    interpreter
+        .interpret(&program)
+        .map_err(CompilationError::Runtime)
+}
 
-    Ok(())
+fn report(error: &CompilationError) {
+    match error {
+        CompilationError::Scan(errors) => {
+            for error in errors {
+                error_at(error.span(), &error.to_string());
+            }
+        }
+        CompilationError::Parse(errors) => {
+            for error in errors {
+                coded_error_at(error.token().span(), error.code(), &error.to_string());
+            }
+        }
+        CompilationError::Runtime(error) => match error.token() {
+            Some(token) => error_at(token.span(), &error.to_string()),
+            None => println!("{}", error),
+        },
+    }
 }
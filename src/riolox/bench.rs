@@ -0,0 +1,65 @@
+// Compares scanning and parsing with tokens that share the source against the
+// owned layout the scanner used to produce, where each token copies its text
+// into an allocation of its own. Both run the scanner and `parse_program` on
+// the same input, so only the layouts differ. Run with:
+//
+//     cargo test --release bench -- --ignored --nocapture
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use super::{
+    parser::Parser,
+    scanner::Scanner,
+    token::{Lexeme, Token},
+};
+
+fn source(lines: usize) -> String {
+    let line = "var total_1 = (a + b) * 42.5 - \"some text\"; // comment\n";
+    line.repeat(lines)
+}
+
+// Gives the token its own copy of its text, and of its literal.
+fn owned(token: Token) -> Token {
+    let lexeme = Lexeme::from(token.lexeme());
+    match token.literal() {
+        Some(literal) => Token::with_value(token.t.clone(), literal.clone(), lexeme, *token.span()),
+        None => Token::new(token.t.clone(), lexeme, *token.span()),
+    }
+}
+
+fn time<F: FnMut()>(runs: u32, mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..runs {
+        f();
+    }
+    start.elapsed() / runs
+}
+
+#[test]
+#[ignore]
+fn shared_source_tokens_against_owned_tokens() {
+    let source = source(20_000);
+    let runs = 10;
+
+    let shared = time(runs, || {
+        let mut parser = Parser::from_tokens(Scanner::new(source.clone()));
+        black_box(parser.parse_program());
+        assert!(!parser.had_error());
+    });
+
+    let owned = time(runs, || {
+        let mut parser = Parser::from_tokens(Scanner::new(source.clone()).map(owned));
+        black_box(parser.parse_program());
+        assert!(!parser.had_error());
+    });
+
+    println!(
+        "{} bytes: shared source {:?}, owned tokens {:?}",
+        source.len(),
+        shared,
+        owned
+    );
+}
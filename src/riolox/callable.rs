@@ -0,0 +1,124 @@
+use std::{any::Any, cell::RefCell, fmt::Display, rc::Rc};
+
+use super::{
+    class::LoxClass, environment::Environment, error::LuxError, interpreter::Interpreter,
+    FunctionDecl,
+};
+
+// How callables are stored as interpreter values, i.e. what to `downcast_ref`
+// a value to when calling it.
+pub(crate) type Callable = Rc<dyn LoxCallable>;
+
+// Anything that can be called from Lox. The interpreter checks the number of
+// arguments against `arity` before calling.
+pub(crate) trait LoxCallable: Display {
+    fn arity(&self) -> usize;
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Rc<dyn Any>>,
+    ) -> Result<Rc<dyn Any>, LuxError>;
+
+    // Classes are callables too, this gets them back out.
+    fn as_class(self: Rc<Self>) -> Option<Rc<LoxClass>> {
+        None
+    }
+}
+
+// A `fun` declaration or method together with the scope it was declared in.
+pub(crate) struct LoxFunction {
+    declaration: Rc<FunctionDecl>,
+    closure: Rc<RefCell<Environment>>,
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(declaration: Rc<FunctionDecl>, closure: Rc<RefCell<Environment>>) -> Self {
+        LoxFunction {
+            declaration,
+            closure,
+            is_initializer: false,
+        }
+    }
+
+    // An `init` method, which returns `this` however it returns.
+    pub fn initializer(declaration: Rc<FunctionDecl>, closure: Rc<RefCell<Environment>>) -> Self {
+        LoxFunction {
+            is_initializer: true,
+            ..Self::new(declaration, closure)
+        }
+    }
+
+    // The method with `this` bound to `instance`.
+    pub fn bind(&self, instance: Rc<dyn Any>) -> LoxFunction {
+        let mut environment = Environment::with_enclosing(self.closure.clone());
+        environment.define("this", instance);
+        LoxFunction {
+            declaration: self.declaration.clone(),
+            closure: Rc::new(RefCell::new(environment)),
+            is_initializer: self.is_initializer,
+        }
+    }
+}
+
+impl LoxCallable for LoxFunction {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Rc<dyn Any>>,
+    ) -> Result<Rc<dyn Any>, LuxError> {
+        let mut environment = Environment::with_enclosing(self.closure.clone());
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(param.lexeme(), argument);
+        }
+
+        let value = match interpreter.execute_block(&self.declaration.body, environment) {
+            Ok(()) => Rc::new(()),
+            Err(LuxError::Return(value)) => value,
+            Err(error) => return Err(error),
+        };
+
+        if self.is_initializer {
+            return Ok(self.closure.borrow().get_here("this").unwrap());
+        }
+        Ok(value)
+    }
+}
+
+impl Display for LoxFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.lexeme())
+    }
+}
+
+type NativeFn = fn(&[Rc<dyn Any>]) -> Rc<dyn Any>;
+
+// A function implemented in Rust, such as `clock`.
+pub(crate) struct NativeFunction {
+    pub arity: usize,
+    pub function: NativeFn,
+}
+
+impl LoxCallable for NativeFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(
+        self: Rc<Self>,
+        _: &mut Interpreter,
+        arguments: Vec<Rc<dyn Any>>,
+    ) -> Result<Rc<dyn Any>, LuxError> {
+        Ok((self.function)(&arguments))
+    }
+}
+
+impl Display for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("<native fn>")
+    }
+}
@@ -0,0 +1,109 @@
+use std::{any::Any, cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
+
+use super::{
+    callable::{Callable, LoxCallable, LoxFunction},
+    error::LuxError,
+    interpreter::Interpreter,
+    token::Token,
+};
+
+// How instances are stored as interpreter values.
+pub(crate) type Instance = Rc<RefCell<LoxInstance>>;
+
+// Calling a class creates an instance and runs its `init` method, if any, with
+// the call's arguments.
+pub(crate) struct LoxClass {
+    name: String,
+    superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<String, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    pub fn new(
+        name: &str,
+        superclass: Option<Rc<LoxClass>>,
+        methods: HashMap<String, Rc<LoxFunction>>,
+    ) -> Self {
+        LoxClass {
+            name: name.to_owned(),
+            superclass,
+            methods,
+        }
+    }
+
+    // Methods are looked up on the class first, then up its superclasses.
+    pub fn find_method(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        match self.methods.get(name) {
+            Some(method) => Some(method.clone()),
+            None => self.superclass.as_ref()?.find_method(name),
+        }
+    }
+}
+
+impl LoxCallable for LoxClass {
+    fn arity(&self) -> usize {
+        self.find_method("init").map_or(0, |init| init.arity())
+    }
+
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Rc<dyn Any>>,
+    ) -> Result<Rc<dyn Any>, LuxError> {
+        let instance: Rc<dyn Any> = Rc::new(Rc::new(RefCell::new(LoxInstance {
+            class: self.clone(),
+            fields: HashMap::new(),
+        })));
+
+        if let Some(init) = self.find_method("init") {
+            Rc::new(init.bind(instance.clone())).call(interpreter, arguments)?;
+        }
+        Ok(instance)
+    }
+
+    fn as_class(self: Rc<Self>) -> Option<Rc<LoxClass>> {
+        Some(self)
+    }
+}
+
+impl Display for LoxClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+pub(crate) struct LoxInstance {
+    class: Rc<LoxClass>,
+    fields: HashMap<String, Rc<dyn Any>>,
+}
+
+impl LoxInstance {
+    // Fields shadow methods. Methods come back bound to `this`, the value
+    // holding this instance.
+    pub fn get(&self, name: &Token, this: &Rc<dyn Any>) -> Result<Rc<dyn Any>, LuxError> {
+        if let Some(value) = self.fields.get(name.lexeme()) {
+            return Ok(value.clone());
+        }
+
+        match self.class.find_method(name.lexeme()) {
+            Some(method) => {
+                let method: Callable = Rc::new(method.bind(this.clone()));
+                Ok(Rc::new(method))
+            }
+            None => Err(LuxError::Property(
+                name.clone().into(),
+                format!("Undefined property '{}'.", name.lexeme()),
+            )),
+        }
+    }
+
+    pub fn set(&mut self, name: &Token, value: Rc<dyn Any>) {
+        self.fields.insert(name.lexeme().to_owned(), value);
+    }
+}
+
+impl Display for LoxInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} instance", self.class)
+    }
+}
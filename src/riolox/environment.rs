@@ -0,0 +1,118 @@
+use std::{any::Any, cell::RefCell, collections::HashMap, rc::Rc};
+
+use super::{error::LuxError, token::Token};
+
+// Variables of one scope. Lookups and assignments that miss here go on to the
+// enclosing scope, up to the globals.
+#[derive(Default)]
+pub(crate) struct Environment {
+    values: HashMap<String, Rc<dyn Any>>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    // Declaring a variable again in the same scope replaces it.
+    pub fn define(&mut self, name: &str, value: Rc<dyn Any>) {
+        self.values.insert(name.to_owned(), value);
+    }
+
+    // Looks `name` up in this scope only.
+    pub fn get_here(&self, name: &str) -> Option<Rc<dyn Any>> {
+        self.values.get(name).cloned()
+    }
+
+    // The scope `distance` scopes out from `environment`, which must have
+    // that many enclosing scopes.
+    pub fn ancestor(
+        environment: &Rc<RefCell<Environment>>,
+        distance: usize,
+    ) -> Rc<RefCell<Environment>> {
+        let mut environment = environment.clone();
+        for _ in 0..distance {
+            let enclosing = environment
+                .borrow()
+                .enclosing
+                .clone()
+                .expect("the resolver counted an enclosing scope");
+            environment = enclosing;
+        }
+        environment
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Rc<dyn Any>, LuxError> {
+        if let Some(value) = self.values.get(name.lexeme()) {
+            return Ok(value.clone());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name),
+            None => Err(LuxError::UndefinedVariable(name.clone().into())),
+        }
+    }
+
+    pub fn assign(&mut self, name: &Token, value: Rc<dyn Any>) -> Result<(), LuxError> {
+        if let Some(slot) = self.values.get_mut(name.lexeme()) {
+            *slot = value;
+            return Ok(());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(LuxError::UndefinedVariable(name.clone().into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::riolox::{span::Span, token::TokenType};
+
+    use super::*;
+
+    fn name(name: &str) -> Token {
+        Token::new(TokenType::Identifier, name, Span::default())
+    }
+
+    fn number(value: Rc<dyn Any>) -> f64 {
+        *value.downcast_ref::<f64>().unwrap()
+    }
+
+    #[test]
+    fn reads_and_assigns_through_enclosing_scopes() {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        globals.borrow_mut().define("a", Rc::new(1.0));
+        let mut local = Environment::with_enclosing(globals.clone());
+        local.define("b", Rc::new(2.0));
+
+        assert_eq!(number(local.get(&name("a")).ok().unwrap()), 1.0);
+
+        local.assign(&name("a"), Rc::new(3.0)).ok().unwrap();
+        local.assign(&name("b"), Rc::new(4.0)).ok().unwrap();
+
+        assert_eq!(number(globals.borrow().get(&name("a")).ok().unwrap()), 3.0);
+        assert_eq!(number(local.get(&name("b")).ok().unwrap()), 4.0);
+        assert!(globals.borrow().get(&name("b")).is_err());
+    }
+
+    #[test]
+    fn undefined_variable() {
+        let mut environment = Environment::new();
+
+        let error = environment.get(&name("x")).err().unwrap();
+        assert_eq!(error.to_string(), "Undefined variable 'x'.");
+
+        let error = environment.assign(&name("x"), Rc::new(())).err().unwrap();
+        assert_eq!(error.to_string(), "Undefined variable 'x'.");
+    }
+}
@@ -1,21 +1,126 @@
-use std::{fmt::Display, rc::Rc};
+use std::{any::Any, fmt::Display, rc::Rc};
 
-use super::token::{Token, TokenType};
+use super::{
+    parser::MAX_ARGUMENTS,
+    span::Span,
+    token::{Token, TokenType},
+};
 
 pub enum LuxError {
-    Parser(ParseError),
-    Interpreter(TokenType, &'static str),
+    // An operator applied to operands of the wrong type. The token is the
+    // operator.
+    Operand(Box<Token>, &'static str),
+    UndefinedVariable(Box<Token>),
+    // Calling something that is not callable, or with the wrong number of
+    // arguments. The token is the call's closing parenthesis.
+    Call(Box<Token>, String),
+    // Reading a property that does not exist, or a property of something that
+    // is not an instance. The token is the property's name.
+    Property(Box<Token>, String),
+    // `class B < A` where `A` is not a class. The token is `A`.
+    Superclass(Box<Token>),
+    // Not an error: unwinds a `return` up to the call it returns from.
+    Return(Rc<dyn Any>),
+    // Not errors either: unwind a `break` or `continue` up to its loop.
+    Break,
+    Continue,
     Runtime,
 }
 
+impl LuxError {
+    // The token the error points at, if it has one.
+    pub fn token(&self) -> Option<&Token> {
+        match self {
+            LuxError::UndefinedVariable(name) => Some(name),
+            LuxError::Call(paren, _) => Some(paren),
+            LuxError::Property(name, _) => Some(name),
+            LuxError::Superclass(name) => Some(name),
+            LuxError::Operand(operator, _) => Some(operator),
+            LuxError::Return(_) | LuxError::Break | LuxError::Continue | LuxError::Runtime => None,
+        }
+    }
+}
+
+// Syntax errors, and the mistakes the parser and the resolver catch without
+// running the code. Every variant has a code from `code` that stays the same
+// when messages are reworded, for tools and tests to match on.
 pub enum ParseError {
-    Generic(Rc<Token>, String),
+    // The grammar requires `expected` here. The message says where, e.g.
+    // "Expected ';' after value.". Only tools and tests read `expected`, the
+    // CLI prints the message.
+    ExpectedToken {
+        #[allow(dead_code)]
+        expected: TokenType,
+        found: Box<Token>,
+        message: String,
+    },
+    ExpectedExpression(Box<Token>),
+    // A binary operator with nothing on its left. The token is the operator.
+    MissingLeftOperand(Box<Token>),
+    // The token is the `=`.
+    InvalidAssignmentTarget(Box<Token>),
+    // The token is the first argument or parameter over the limit.
+    TooManyArguments(Box<Token>),
+    TooManyParameters(Box<Token>),
+    // `,` or `?` in a dialect that switched the operator off.
+    DisabledOperator(Box<Token>),
+    ReturnOutsideFunction(Box<Token>),
+    ReturnValueFromInitializer(Box<Token>),
+    ThisOutsideClass(Box<Token>),
+    SuperOutsideClass(Box<Token>),
+    SuperWithoutSuperclass(Box<Token>),
+    // The token is the superclass name.
+    InheritFromSelf(Box<Token>),
+    // `break` or `continue` outside of a loop, the token says which.
+    OutsideLoop(Box<Token>),
+    // A local variable or parameter declared twice in one scope. The token is
+    // the second declaration.
+    AlreadyDeclared(Box<Token>),
+    // `var a = a;` in a local scope.
+    OwnInitializer(Box<Token>),
 }
 
 impl ParseError {
     pub fn token(&self) -> &Token {
         match self {
-            ParseError::Generic(token, _) => token,
+            ParseError::ExpectedToken { found, .. } => found,
+            ParseError::ExpectedExpression(token)
+            | ParseError::MissingLeftOperand(token)
+            | ParseError::InvalidAssignmentTarget(token)
+            | ParseError::TooManyArguments(token)
+            | ParseError::TooManyParameters(token)
+            | ParseError::DisabledOperator(token)
+            | ParseError::ReturnOutsideFunction(token)
+            | ParseError::ReturnValueFromInitializer(token)
+            | ParseError::ThisOutsideClass(token)
+            | ParseError::SuperOutsideClass(token)
+            | ParseError::SuperWithoutSuperclass(token)
+            | ParseError::InheritFromSelf(token)
+            | ParseError::OutsideLoop(token)
+            | ParseError::AlreadyDeclared(token)
+            | ParseError::OwnInitializer(token) => token,
+        }
+    }
+
+    // Codes are never reused or renumbered, new variants get the next one.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::ExpectedToken { .. } => "P001",
+            ParseError::ExpectedExpression(_) => "P002",
+            ParseError::MissingLeftOperand(_) => "P003",
+            ParseError::InvalidAssignmentTarget(_) => "P004",
+            ParseError::TooManyArguments(_) => "P005",
+            ParseError::TooManyParameters(_) => "P006",
+            ParseError::DisabledOperator(_) => "P007",
+            ParseError::ReturnOutsideFunction(_) => "P008",
+            ParseError::ReturnValueFromInitializer(_) => "P009",
+            ParseError::ThisOutsideClass(_) => "P010",
+            ParseError::SuperOutsideClass(_) => "P011",
+            ParseError::SuperWithoutSuperclass(_) => "P012",
+            ParseError::InheritFromSelf(_) => "P013",
+            ParseError::OutsideLoop(_) => "P014",
+            ParseError::AlreadyDeclared(_) => "P015",
+            ParseError::OwnInitializer(_) => "P016",
         }
     }
 }
@@ -23,7 +128,111 @@ impl ParseError {
 impl Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ParseError::Generic(_, msg) => f.write_str(msg),
+            ParseError::ExpectedToken { message, .. } => f.write_str(message),
+            ParseError::ExpectedExpression(_) => f.write_str("Expected expression."),
+            ParseError::MissingLeftOperand(_) => f.write_str("Expected left hand operand."),
+            ParseError::InvalidAssignmentTarget(_) => f.write_str("Invalid assignment target."),
+            ParseError::TooManyArguments(_) => {
+                write!(f, "Can't have more than {MAX_ARGUMENTS} arguments.")
+            }
+            ParseError::TooManyParameters(_) => {
+                write!(f, "Can't have more than {MAX_ARGUMENTS} parameters.")
+            }
+            ParseError::DisabledOperator(token) => match token.t {
+                TokenType::Comma => {
+                    f.write_str("The comma operator is not enabled in this dialect.")
+                }
+                _ => f.write_str("The '?:' operator is not enabled in this dialect."),
+            },
+            ParseError::ReturnOutsideFunction(_) => {
+                f.write_str("Can't return from top-level code.")
+            }
+            ParseError::ReturnValueFromInitializer(_) => {
+                f.write_str("Can't return a value from an initializer.")
+            }
+            ParseError::ThisOutsideClass(_) => f.write_str("Can't use 'this' outside of a class."),
+            ParseError::SuperOutsideClass(_) => {
+                f.write_str("Can't use 'super' outside of a class.")
+            }
+            ParseError::SuperWithoutSuperclass(_) => {
+                f.write_str("Can't use 'super' in a class with no superclass.")
+            }
+            ParseError::InheritFromSelf(_) => f.write_str("A class can't inherit from itself."),
+            ParseError::OutsideLoop(keyword) => {
+                write!(f, "Can't use '{}' outside of a loop.", keyword.lexeme())
+            }
+            ParseError::AlreadyDeclared(_) => {
+                f.write_str("Already a variable with this name in this scope.")
+            }
+            ParseError::OwnInitializer(_) => {
+                f.write_str("Can't read local variable in its own initializer.")
+            }
+        }
+    }
+}
+
+impl Display for LuxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LuxError::Operand(_, msg) => f.write_str(msg),
+            LuxError::UndefinedVariable(name) => {
+                write!(f, "Undefined variable '{}'.", name.lexeme())
+            }
+            LuxError::Call(_, msg) | LuxError::Property(_, msg) => f.write_str(msg),
+            LuxError::Superclass(_) => f.write_str("Superclass must be a class."),
+            LuxError::Return(_) => f.write_str("Can't return from top-level code."),
+            LuxError::Break => f.write_str("Can't use 'break' outside of a loop."),
+            LuxError::Continue => f.write_str("Can't use 'continue' outside of a loop."),
+            LuxError::Runtime => f.write_str("Runtime error."),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ScanError {
+    UnexpectedCharacter(Span, char),
+    UnterminatedString(Span),
+    UnterminatedInterpolation(Span),
+    UnterminatedBlockComment(Span),
+    UnknownEscape(Span, char),
+    InvalidUnicodeEscape(Span, String),
+    InvalidNumber(Span, String),
+    Read(Span, String),
+}
+
+impl ScanError {
+    pub fn span(&self) -> &Span {
+        match self {
+            ScanError::UnexpectedCharacter(span, _)
+            | ScanError::UnterminatedString(span)
+            | ScanError::UnterminatedInterpolation(span)
+            | ScanError::UnterminatedBlockComment(span)
+            | ScanError::UnknownEscape(span, _)
+            | ScanError::InvalidUnicodeEscape(span, _)
+            | ScanError::InvalidNumber(span, _)
+            | ScanError::Read(span, _) => span,
+        }
+    }
+}
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScanError::UnexpectedCharacter(_, c) => {
+                write!(f, "Unexpected character '{}'.", c.escape_default())
+            }
+            ScanError::UnterminatedString(_) => f.write_str("Unterminated string."),
+            ScanError::UnterminatedInterpolation(_) => {
+                f.write_str("Unterminated string interpolation.")
+            }
+            ScanError::UnterminatedBlockComment(_) => f.write_str("Unterminated block comment."),
+            ScanError::UnknownEscape(_, c) => {
+                write!(f, "Unknown escape sequence '\\{}'.", c.escape_default())
+            }
+            ScanError::InvalidUnicodeEscape(_, msg) | ScanError::InvalidNumber(_, msg) => {
+                f.write_str(msg)
+            }
+            ScanError::Read(_, msg) => write!(f, "Failed to read source: {}.", msg),
         }
     }
 }
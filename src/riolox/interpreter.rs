@@ -1,22 +1,44 @@
-use std::any::Any;
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::HashMap,
+    io::{stdout, Write},
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-type InterpreterResult = Result<Box<dyn Any>, super::error::LuxError>;
+type InterpreterResult = Result<Rc<dyn Any>, super::error::LuxError>;
 use super::{
+    callable::{Callable, LoxFunction, NativeFunction},
+    class::{Instance, LoxClass},
+    environment::Environment,
     error::LuxError,
-    token::{TokenLiteral, TokenType},
-    visitor::{SafeVisitor, Visitor},
-    Expr,
+    token::{Token, TokenLiteral, TokenType},
+    visitor::SafeVisitor,
+    Expr, Stmt,
 };
 
-struct Interpreter;
+// Values are `String`, `f64`, `bool`, `()` for nil, a `Callable` (functions
+// and classes) or an `Instance`.
+pub(crate) struct Interpreter {
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    // How many scopes out each local variable expression finds its variable,
+    // from the resolver. The `Rc` keeps the expression alive so its address
+    // isn't reused by a later prompt line while the entry is here.
+    locals: HashMap<*const Expr, (usize, Rc<Expr>)>,
+    out: Box<dyn Write>,
+}
 
-impl SafeVisitor<Box<dyn Any>> for Interpreter {
-    fn visit(&self, expr: &Expr) -> InterpreterResult {
+// Evaluating a call runs statements, so expressions are visited mutably.
+impl SafeVisitor<Rc<dyn Any>> for Interpreter {
+    fn visit_mut(&mut self, expr: &Expr) -> InterpreterResult {
         match expr {
             Expr::Literal(val) => match val {
-                TokenLiteral::Str(v) => Ok(Box::new(v.clone())),
-                TokenLiteral::Num(v) => Ok(Box::new(*v)),
-                TokenLiteral::Bool(v) => Ok(Box::new(*v)),
+                TokenLiteral::Str(v) => Ok(Rc::new(v.clone())),
+                TokenLiteral::Num(v) => Ok(Rc::new(*v)),
+                TokenLiteral::Bool(v) => Ok(Rc::new(*v)),
+                TokenLiteral::Nil => Ok(Rc::new(())),
             },
             Expr::Grouping(expr) => self.evaluate(expr),
             Expr::Unary(token, expr) => {
@@ -24,10 +46,10 @@ impl SafeVisitor<Box<dyn Any>> for Interpreter {
 
                 match token.t {
                     TokenType::Minus => {
-                        self.check_num_operand(TokenType::Minus, c.as_ref())?;
-                        Ok(Box::new(-c.downcast_ref::<f64>().unwrap()))
+                        self.check_num_operand(token, c.as_ref())?;
+                        Ok(Rc::new(-c.downcast_ref::<f64>().unwrap()))
                     }
-                    TokenType::Bang => Ok(Box::new(!c.downcast_ref::<bool>().unwrap())),
+                    TokenType::Bang => Ok(Rc::new(!self.is_truthy(c.as_ref()))),
                     _ => todo!(),
                 }
             }
@@ -36,154 +58,1169 @@ impl SafeVisitor<Box<dyn Any>> for Interpreter {
                 let right = self.evaluate(b)?;
 
                 match token.t {
+                    // Both sides are evaluated already, the left one only for
+                    // its effects.
+                    TokenType::Comma => Ok(right),
                     TokenType::Minus => {
-                        self.check_num_operands(TokenType::Minus, left.as_ref(), right.as_ref())?;
-                        Ok(Box::new(
+                        self.check_num_operands(token, left.as_ref(), right.as_ref())?;
+                        Ok(Rc::new(
                             left.downcast_ref::<f64>().unwrap()
                                 - right.downcast_ref::<f64>().unwrap(),
                         ))
                     }
                     TokenType::Slash => {
-                        self.check_num_operands(TokenType::Slash, left.as_ref(), right.as_ref())?;
-                        Ok(Box::new(
+                        self.check_num_operands(token, left.as_ref(), right.as_ref())?;
+                        Ok(Rc::new(
                             left.downcast_ref::<f64>().unwrap()
                                 / right.downcast_ref::<f64>().unwrap(),
                         ))
                     }
                     TokenType::Star => {
-                        self.check_num_operands(TokenType::Slash, left.as_ref(), right.as_ref())?;
-                        Ok(Box::new(
+                        self.check_num_operands(token, left.as_ref(), right.as_ref())?;
+                        Ok(Rc::new(
                             left.downcast_ref::<f64>().unwrap()
                                 * right.downcast_ref::<f64>().unwrap(),
                         ))
                     }
                     TokenType::Plus => {
                         if left.is::<String>() && right.is::<String>() {
-                            Ok(Box::new(format!(
+                            Ok(Rc::new(format!(
                                 "{}{}",
                                 left.downcast_ref::<String>().unwrap(),
                                 right.downcast_ref::<String>().unwrap()
                             )))
-                        } else if left.is::<f64>() && left.is::<f64>() {
-                            self.check_num_operands(
-                                TokenType::Plus,
-                                left.as_ref(),
-                                right.as_ref(),
-                            )?;
-                            Ok(Box::new(
+                        } else if left.is::<f64>() && right.is::<f64>() {
+                            Ok(Rc::new(
                                 left.downcast_ref::<f64>().unwrap()
                                     + right.downcast_ref::<f64>().unwrap(),
                             ))
                         } else {
-                            return Err(LuxError::Interpreter(
-                                TokenType::Plus,
+                            Err(LuxError::Operand(
+                                token.clone().into(),
                                 "Operand must be either numbers or strings.",
-                            ));
+                            ))
                         }
                     }
                     TokenType::Greater => {
-                        self.check_num_operands(TokenType::Greater, left.as_ref(), right.as_ref())?;
-                        Ok(Box::new(
+                        self.check_num_operands(token, left.as_ref(), right.as_ref())?;
+                        Ok(Rc::new(
                             left.downcast_ref::<f64>().unwrap()
                                 > right.downcast_ref::<f64>().unwrap(),
                         ))
                     }
                     TokenType::GreaterEqual => {
-                        self.check_num_operands(
-                            TokenType::GreaterEqual,
-                            left.as_ref(),
-                            right.as_ref(),
-                        )?;
-                        Ok(Box::new(
+                        self.check_num_operands(token, left.as_ref(), right.as_ref())?;
+                        Ok(Rc::new(
                             left.downcast_ref::<f64>().unwrap()
                                 >= right.downcast_ref::<f64>().unwrap(),
                         ))
                     }
                     TokenType::Less => {
-                        self.check_num_operands(TokenType::Less, left.as_ref(), right.as_ref())?;
-                        Ok(Box::new(
+                        self.check_num_operands(token, left.as_ref(), right.as_ref())?;
+                        Ok(Rc::new(
                             left.downcast_ref::<f64>().unwrap()
                                 < right.downcast_ref::<f64>().unwrap(),
                         ))
                     }
                     TokenType::LessEqual => {
-                        self.check_num_operands(
-                            TokenType::LessEqual,
-                            left.as_ref(),
-                            right.as_ref(),
-                        )?;
-                        Ok(Box::new(
+                        self.check_num_operands(token, left.as_ref(), right.as_ref())?;
+                        Ok(Rc::new(
                             left.downcast_ref::<f64>().unwrap()
                                 <= right.downcast_ref::<f64>().unwrap(),
                         ))
                     }
-                    TokenType::Equal => {
-                        self.check_num_operands(
-                            TokenType::LessEqual,
-                            left.as_ref(),
-                            right.as_ref(),
-                        )?;
-                        Ok(Box::new(
-                            left.downcast_ref::<f64>().unwrap()
-                                == right.downcast_ref::<f64>().unwrap(),
-                        ))
+                    TokenType::EqualEqual => {
+                        Ok(Rc::new(self.is_equal(left.as_ref(), right.as_ref())))
                     }
                     TokenType::BangEqual => {
-                        self.check_num_operands(
-                            TokenType::LessEqual,
-                            left.as_ref(),
-                            right.as_ref(),
-                        )?;
-                        Ok(Box::new(
-                            left.downcast_ref::<f64>().unwrap()
-                                != right.downcast_ref::<f64>().unwrap(),
-                        ))
+                        Ok(Rc::new(!self.is_equal(left.as_ref(), right.as_ref())))
                     }
                     _ => panic!("Unsupported binary operation"),
                 }
             }
+            Expr::Logical(left, operator, right) => {
+                let left = self.evaluate(left)?;
+                let truthy = self.is_truthy(left.as_ref());
+
+                match operator.t {
+                    TokenType::Or if truthy => Ok(left),
+                    TokenType::And if !truthy => Ok(left),
+                    _ => self.evaluate(right),
+                }
+            }
             Expr::Conditional(truthy, a, b) => {
                 let predicate = self.evaluate(truthy)?;
 
-                if *predicate.downcast_ref::<bool>().unwrap() {
+                if self.is_truthy(predicate.as_ref()) {
                     self.evaluate(a)
                 } else {
                     self.evaluate(b)
                 }
             }
+            Expr::Interpolation(parts) => {
+                let mut value = String::new();
+                for part in parts {
+                    let part = self.evaluate(part)?;
+                    value.push_str(&self.stringify(part.as_ref()));
+                }
+                Ok(Rc::new(value))
+            }
+            Expr::Variable(name) => self.look_up_variable(name, expr),
+            Expr::Assign(name, value) => {
+                let value = self.evaluate(value)?;
+                match self.locals.get(&(expr as *const Expr)) {
+                    Some((depth, _)) => Environment::ancestor(&self.environment, *depth)
+                        .borrow_mut()
+                        .assign(name, value.clone())?,
+                    None => self.globals.borrow_mut().assign(name, value.clone())?,
+                }
+                Ok(value)
+            }
+            Expr::Call(callee, paren, arguments) => {
+                let callee = self.evaluate(callee)?;
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.evaluate(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let Some(function) = callee.downcast_ref::<Callable>() else {
+                    return Err(LuxError::Call(
+                        paren.clone().into(),
+                        "Can only call functions and classes.".to_owned(),
+                    ));
+                };
+                if arguments.len() != function.arity() {
+                    return Err(LuxError::Call(
+                        paren.clone().into(),
+                        format!(
+                            "Expected {} arguments but got {}.",
+                            function.arity(),
+                            arguments.len()
+                        ),
+                    ));
+                }
+
+                function.clone().call(self, arguments)
+            }
+            Expr::Get(object, name) => {
+                let object = self.evaluate(object)?;
+                match object.downcast_ref::<Instance>() {
+                    Some(instance) => instance.borrow().get(name, &object),
+                    None => Err(LuxError::Property(
+                        name.clone().into(),
+                        "Only instances have properties.".to_owned(),
+                    )),
+                }
+            }
+            Expr::Set(object, name, value) => {
+                let object = self.evaluate(object)?;
+                let Some(instance) = object.downcast_ref::<Instance>() else {
+                    return Err(LuxError::Property(
+                        name.clone().into(),
+                        "Only instances have fields.".to_owned(),
+                    ));
+                };
+
+                let value = self.evaluate(value)?;
+                instance.borrow_mut().set(name, value.clone());
+                Ok(value)
+            }
+            Expr::This(keyword) => self.look_up_variable(keyword, expr),
+            Expr::Super(keyword, method) => {
+                // `super` is defined in the scope around the methods of a
+                // subclass, `this` in the scope of the bound method just
+                // inside it.
+                let (depth, _) = self.locals[&(expr as *const Expr)];
+                let superclass = Environment::ancestor(&self.environment, depth)
+                    .borrow()
+                    .get(keyword)?;
+                let superclass = superclass
+                    .downcast_ref::<Callable>()
+                    .and_then(|class| class.clone().as_class())
+                    .expect("'super' is always bound to a class");
+                let this = Token::new(TokenType::This, "this", *keyword.span());
+                let instance = Environment::ancestor(&self.environment, depth - 1)
+                    .borrow()
+                    .get(&this)?;
+
+                match superclass.find_method(method.lexeme()) {
+                    Some(function) => {
+                        let bound: Callable = Rc::new(function.bind(instance));
+                        Ok(Rc::new(bound))
+                    }
+                    None => Err(LuxError::Property(
+                        method.clone().into(),
+                        format!("Undefined property '{}'.", method.lexeme()),
+                    )),
+                }
+            }
         }
     }
+}
 
-    fn visit_mut(&mut self, _: &Expr) -> InterpreterResult {
-        Err(LuxError::Runtime)
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Interpreter {
-    pub fn evaluate(&self, expr: &Expr) -> InterpreterResult {
-        self.visit(expr)
+    pub fn new() -> Self {
+        Self::with_output(stdout())
     }
 
-    fn check_num_operand(&self, token: TokenType, operand: &dyn Any) -> Result<(), LuxError> {
+    // `print` writes to `out` instead of stdout.
+    pub fn with_output<W: Write + 'static>(out: W) -> Self {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        let clock: Callable = Rc::new(NativeFunction {
+            arity: 0,
+            function: |_| {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+                Rc::new(now.as_secs_f64())
+            },
+        });
+        globals.borrow_mut().define("clock", Rc::new(clock));
+
+        Interpreter {
+            environment: globals.clone(),
+            globals,
+            locals: HashMap::new(),
+            out: Box::new(out),
+        }
+    }
+
+    // Called by the resolver for each local variable expression: `expr`'s
+    // variable is declared `depth` scopes out from where it runs.
+    pub fn resolve(&mut self, expr: &Rc<Expr>, depth: usize) {
+        self.locals.insert(Rc::as_ptr(expr), (depth, expr.clone()));
+    }
+
+    // Locals are read from the scope the resolver found them in, anything
+    // else is a global.
+    fn look_up_variable(&self, name: &Token, expr: &Expr) -> InterpreterResult {
+        match self.locals.get(&(expr as *const Expr)) {
+            Some((depth, _)) => Environment::ancestor(&self.environment, *depth)
+                .borrow()
+                .get(name),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    // Runs the statements in order, stopping at the first runtime error.
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<(), LuxError> {
+        for statement in statements {
+            self.execute(statement)?;
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), LuxError> {
+        match stmt {
+            Stmt::Expression(expr) => {
+                self.evaluate(expr)?;
+            }
+            Stmt::Print(expr) => {
+                let value = self.evaluate(expr)?;
+                let text = self.stringify(value.as_ref());
+                writeln!(self.out, "{}", text).map_err(|_| LuxError::Runtime)?;
+            }
+            Stmt::Var(name, initializer, _) => {
+                let value = match initializer {
+                    Some(initializer) => self.evaluate(initializer)?,
+                    None => Rc::new(()),
+                };
+                self.environment.borrow_mut().define(name.lexeme(), value);
+            }
+            Stmt::Block(statements) => {
+                let scope = Environment::with_enclosing(self.environment.clone());
+                self.execute_block(statements, scope)?;
+            }
+            Stmt::If(condition, then_branch, else_branch) => {
+                let condition = self.evaluate(condition)?;
+                if self.is_truthy(condition.as_ref()) {
+                    self.execute(then_branch)?;
+                } else if let Some(else_branch) = else_branch {
+                    self.execute(else_branch)?;
+                }
+            }
+            Stmt::While(condition, body, increment) => loop {
+                let value = self.evaluate(condition)?;
+                if !self.is_truthy(value.as_ref()) {
+                    break;
+                }
+                match self.execute(body) {
+                    Ok(()) | Err(LuxError::Continue) => {}
+                    Err(LuxError::Break) => break,
+                    Err(error) => return Err(error),
+                }
+                if let Some(increment) = increment {
+                    self.evaluate(increment)?;
+                }
+            },
+            Stmt::Function(declaration) => {
+                let function: Callable = Rc::new(LoxFunction::new(
+                    declaration.clone(),
+                    self.environment.clone(),
+                ));
+                self.environment
+                    .borrow_mut()
+                    .define(declaration.name.lexeme(), Rc::new(function));
+            }
+            Stmt::Class(declaration) => {
+                let superclass = match &declaration.superclass {
+                    Some(expr) => {
+                        let value = self.evaluate(expr)?;
+                        let class = value
+                            .downcast_ref::<Callable>()
+                            .and_then(|callable| callable.clone().as_class());
+                        match (class, expr.as_ref()) {
+                            (Some(class), _) => Some((class, value)),
+                            (None, Expr::Variable(name)) => {
+                                return Err(LuxError::Superclass(name.clone().into()))
+                            }
+                            (None, _) => unreachable!("the superclass is always a variable"),
+                        }
+                    }
+                    None => None,
+                };
+
+                // Methods of a subclass close over a scope that binds `super`.
+                let enclosing = self.environment.clone();
+                if let Some((_, value)) = &superclass {
+                    let mut environment = Environment::with_enclosing(enclosing.clone());
+                    environment.define("super", value.clone());
+                    self.environment = Rc::new(RefCell::new(environment));
+                }
+
+                let methods = declaration
+                    .methods
+                    .iter()
+                    .map(|method| {
+                        let closure = self.environment.clone();
+                        let function = if method.name.lexeme() == "init" {
+                            LoxFunction::initializer(method.clone(), closure)
+                        } else {
+                            LoxFunction::new(method.clone(), closure)
+                        };
+                        (method.name.lexeme().to_owned(), Rc::new(function))
+                    })
+                    .collect();
+
+                self.environment = enclosing;
+
+                let superclass = superclass.map(|(class, _)| class);
+                let class: Callable = Rc::new(LoxClass::new(
+                    declaration.name.lexeme(),
+                    superclass,
+                    methods,
+                ));
+                self.environment
+                    .borrow_mut()
+                    .define(declaration.name.lexeme(), Rc::new(class));
+            }
+            Stmt::Return(_, value) => {
+                let value = match value {
+                    Some(value) => self.evaluate(value)?,
+                    None => Rc::new(()),
+                };
+                return Err(LuxError::Return(value));
+            }
+            Stmt::Break => return Err(LuxError::Break),
+            Stmt::Continue => return Err(LuxError::Continue),
+        }
+        Ok(())
+    }
+
+    // Runs `statements` in `environment`, then goes back to the current one
+    // whether they finished or stopped at an error.
+    pub fn execute_block(
+        &mut self,
+        statements: &[Stmt],
+        environment: Environment,
+    ) -> Result<(), LuxError> {
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+        let result = statements
+            .iter()
+            .try_for_each(|statement| self.execute(statement));
+        self.environment = previous;
+        result
+    }
+
+    pub fn evaluate(&mut self, expr: &Expr) -> InterpreterResult {
+        self.visit_mut(expr)
+    }
+
+    pub fn stringify(&self, value: &dyn Any) -> String {
+        if let Some(s) = value.downcast_ref::<String>() {
+            s.clone()
+        } else if let Some(n) = value.downcast_ref::<f64>() {
+            n.to_string()
+        } else if let Some(b) = value.downcast_ref::<bool>() {
+            b.to_string()
+        } else if let Some(function) = value.downcast_ref::<Callable>() {
+            function.to_string()
+        } else if let Some(instance) = value.downcast_ref::<Instance>() {
+            instance.borrow().to_string()
+        } else {
+            "nil".to_owned()
+        }
+    }
+
+    // `nil` and `false` are falsey, everything else is truthy.
+    pub fn is_truthy(&self, value: &dyn Any) -> bool {
+        if let Some(b) = value.downcast_ref::<bool>() {
+            *b
+        } else {
+            !value.is::<()>()
+        }
+    }
+
+    // Values of different types are never equal. Functions, classes and
+    // instances are only equal to themselves.
+    fn is_equal(&self, left: &dyn Any, right: &dyn Any) -> bool {
+        if left.is::<()>() && right.is::<()>() {
+            true
+        } else if let (Some(a), Some(b)) =
+            (left.downcast_ref::<bool>(), right.downcast_ref::<bool>())
+        {
+            a == b
+        } else if let (Some(a), Some(b)) = (left.downcast_ref::<f64>(), right.downcast_ref::<f64>())
+        {
+            a == b
+        } else if let (Some(a), Some(b)) = (
+            left.downcast_ref::<String>(),
+            right.downcast_ref::<String>(),
+        ) {
+            a == b
+        } else if let (Some(a), Some(b)) = (
+            left.downcast_ref::<Callable>(),
+            right.downcast_ref::<Callable>(),
+        ) {
+            Rc::ptr_eq(a, b)
+        } else if let (Some(a), Some(b)) = (
+            left.downcast_ref::<Instance>(),
+            right.downcast_ref::<Instance>(),
+        ) {
+            Rc::ptr_eq(a, b)
+        } else {
+            false
+        }
+    }
+
+    fn check_num_operand(&self, operator: &Token, operand: &dyn Any) -> Result<(), LuxError> {
         if !operand.is::<f64>() {
-            return Err(LuxError::Interpreter(token, "Operand must be a number."));
+            return Err(LuxError::Operand(
+                operator.clone().into(),
+                "Operand must be a number.",
+            ));
         }
         Ok(())
     }
 
     fn check_num_operands(
         &self,
-        token: TokenType,
+        operator: &Token,
         left: &dyn Any,
         right: &dyn Any,
     ) -> Result<(), LuxError> {
         if !left.is::<f64>() || !right.is::<f64>() {
-            return Err(LuxError::Interpreter(token, "Operands must be numbers."));
+            return Err(LuxError::Operand(
+                operator.clone().into(),
+                "Operands must be numbers.",
+            ));
         }
         Ok(())
     }
 }
 
-trait Test {
-    fn test(&self);
+#[cfg(test)]
+mod tests {
+    use crate::riolox::{parser::Parser, resolver::Resolver, scanner::Scanner};
+
+    use super::*;
+
+    fn evaluate(source: &str) -> Rc<dyn Any> {
+        let mut scanner = Scanner::new(source.to_owned());
+        let tokens = scanner.scan_tokens().to_vec();
+        let expr = Parser::new(tokens).parse().unwrap();
+        Interpreter::new().evaluate(&expr).ok().unwrap()
+    }
+
+    // Collects what `print` writes so tests can look at it.
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // What the program printed, and the runtime error it stopped at.
+    fn try_run(source: &str) -> (String, Result<(), LuxError>) {
+        let mut scanner = Scanner::new(source.to_owned());
+        let tokens = scanner.scan_tokens().to_vec();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse_program();
+        assert!(!parser.had_error(), "{}", source);
+
+        let output = Output::default();
+        let mut interpreter = Interpreter::with_output(output.clone());
+        let errors = Resolver::new(&mut interpreter).resolve(&program);
+        assert!(errors.is_empty(), "{}", source);
+        let result = interpreter.interpret(&program);
+
+        let printed = output.0.borrow();
+        (String::from_utf8(printed.clone()).unwrap(), result)
+    }
+
+    fn run(source: &str) -> String {
+        let (printed, result) = try_run(source);
+        if let Err(error) = result {
+            panic!("{}: {}", source, error);
+        }
+        printed
+    }
+
+    #[test]
+    fn print_statements() {
+        assert_eq!(
+            run("print \"one\"; print 1 + 2; print nil; print 1 == 1;"),
+            "one\n3\nnil\ntrue\n"
+        );
+    }
+
+    #[test]
+    fn statements_run_in_order() {
+        assert_eq!(
+            run("var a = \"x\"; 1 + 2; print \"${4 * 2}\"; var b; print 3;"),
+            "8\n3\n"
+        );
+    }
+
+    #[test]
+    fn string_interpolation() {
+        let value = evaluate(r#""Hello ${"Ann"}, you have ${2 + 1} items""#);
+
+        assert_eq!(
+            value.downcast_ref::<String>().unwrap(),
+            "Hello Ann, you have 3 items"
+        );
+    }
+
+    #[test]
+    fn nested_string_interpolation() {
+        let value = evaluate(r#""${"inner ${1 > 2}"}!""#);
+
+        assert_eq!(value.downcast_ref::<String>().unwrap(), "inner false!");
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(
+            run("var a = 1; var b; print b; b = a = a + 2; print a; print b; var a = \"again\"; print a;"),
+            "nil\n3\n3\nagain\n"
+        );
+    }
+
+    #[test]
+    fn assignment_is_an_expression() {
+        assert_eq!(run("var a; print a = \"value\";"), "value\n");
+    }
+
+    #[test]
+    fn undefined_variable_reports_its_line() {
+        let (printed, result) = try_run("var a = 1;\nprint a;\nprint a + b;");
+
+        assert_eq!(printed, "1\n");
+        let error = result.err().unwrap();
+        assert_eq!(error.to_string(), "Undefined variable 'b'.");
+        assert_eq!(error.token().unwrap().line(), 3);
+
+        let (_, result) = try_run("\n\nc = 1;");
+        let error = result.err().unwrap();
+        assert_eq!(error.to_string(), "Undefined variable 'c'.");
+        assert_eq!(error.token().unwrap().line(), 3);
+    }
+
+    #[test]
+    fn operand_errors_point_at_the_operator() {
+        let (_, result) = try_run("print 1;\nprint -\"a\";");
+        let error = result.err().unwrap();
+        assert_eq!(error.to_string(), "Operand must be a number.");
+        let operator = error.token().unwrap();
+        assert_eq!((operator.lexeme(), operator.line()), ("-", 2));
+
+        let (_, result) = try_run("\n\nprint 1 + nil;");
+        let error = result.err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Operand must be either numbers or strings."
+        );
+        let operator = error.token().unwrap();
+        assert_eq!((operator.lexeme(), operator.line()), ("+", 3));
+        assert_eq!(operator.span().column, 9);
+    }
+
+    #[test]
+    fn equality() {
+        let source = r#"
+            var x;
+            print x == nil;
+            print "a" == "a";
+            print "a" != "b";
+            print 1 == 1;
+            print 1 == "1";
+            print nil == false;
+            print true == true;
+            fun f() {}
+            var g = f;
+            print g == f;
+            fun h() {}
+            print f == h;
+            class A {}
+            var a = A();
+            var b = a;
+            print a == b;
+            print a == A();
+            print A == A;
+        "#;
+
+        assert_eq!(
+            run(source),
+            "true\ntrue\ntrue\ntrue\nfalse\nfalse\ntrue\ntrue\nfalse\ntrue\nfalse\ntrue\n"
+        );
+    }
+
+    #[test]
+    fn comma_operator() {
+        assert_eq!(run("print (1, 2);"), "2\n");
+        assert_eq!(
+            run("var a = 1; print (a = a + 1, a * 10); print a;"),
+            "20\n2\n"
+        );
+    }
+
+    #[test]
+    fn block_scope() {
+        let source = r#"
+            var a = "outer";
+            {
+              var a = "inner";
+              print a;
+            }
+            print a;
+        "#;
+        assert_eq!(run(source), "inner\nouter\n");
+    }
+
+    #[test]
+    fn scope_reuse_in_different_blocks() {
+        let source = r#"
+            {
+              var a = "first";
+              print a;
+            }
+            {
+              var a = "second";
+              print a;
+            }
+        "#;
+        assert_eq!(run(source), "first\nsecond\n");
+    }
+
+    #[test]
+    fn shadow_local() {
+        let source = r#"
+            {
+              var a = "local";
+              {
+                var a = "shadow";
+                print a;
+              }
+              print a;
+            }
+        "#;
+        assert_eq!(run(source), "shadow\nlocal\n");
+    }
+
+    #[test]
+    fn shadow_global() {
+        let source = r#"
+            var a = "global";
+            {
+              var a = "shadow";
+              print a;
+            }
+            print a;
+        "#;
+        assert_eq!(run(source), "shadow\nglobal\n");
+    }
+
+    #[test]
+    fn in_nested_block() {
+        let source = r#"
+            {
+              var a = "outer";
+              {
+                print a;
+              }
+            }
+        "#;
+        assert_eq!(run(source), "outer\n");
+    }
+
+    #[test]
+    fn assign_local_and_enclosing() {
+        let source = r#"
+            var g = "global";
+            {
+              var a = "before";
+              print a;
+              a = "after";
+              print a;
+              print a = "arg";
+              print a;
+              g = "changed";
+            }
+            print g;
+        "#;
+        assert_eq!(run(source), "before\nafter\narg\narg\nchanged\n");
+    }
+
+    #[test]
+    fn block_locals_do_not_leak() {
+        let (printed, result) = try_run("{ var a = 1; print a; } print a;");
+
+        assert_eq!(printed, "1\n");
+        assert_eq!(result.err().unwrap().to_string(), "Undefined variable 'a'.");
+    }
+
+    #[test]
+    fn scope_is_restored_after_error() {
+        let program = |source: &str| {
+            let mut scanner = Scanner::new(source.to_owned());
+            Parser::new(scanner.scan_tokens().to_vec()).parse_program()
+        };
+        let output = Output::default();
+        let mut interpreter = Interpreter::with_output(output.clone());
+
+        interpreter
+            .interpret(&program("var a = \"outer\";"))
+            .ok()
+            .unwrap();
+        let error = interpreter
+            .interpret(&program("{ var a = \"inner\"; { print -a; } }"))
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "Operand must be a number.");
+        interpreter.interpret(&program("print a;")).ok().unwrap();
+
+        assert_eq!(output.0.borrow().as_slice(), b"outer\n");
+    }
+
+    #[test]
+    fn truthiness() {
+        let source = r#"
+            if (nil) print "bad"; else print "nil is false";
+            if (false) print "bad"; else print "false is false";
+            if (0) print "0 is true";
+            if ("") print "empty string is true";
+            print !nil;
+            print !0;
+            print "" ? "yes" : "no";
+        "#;
+        assert_eq!(
+            run(source),
+            "nil is false\nfalse is false\n0 is true\nempty string is true\ntrue\nfalse\nyes\n"
+        );
+    }
+
+    #[test]
+    fn dangling_else() {
+        let source = r#"
+            if (true) if (false) print "bad"; else print "inner else";
+            if (false) if (true) print "bad"; else print "bad";
+        "#;
+        assert_eq!(run(source), "inner else\n");
+    }
+
+    #[test]
+    fn while_loop() {
+        let source = r#"
+            var i = 0;
+            while (i < 3) {
+              print i;
+              i = i + 1;
+            }
+            while (false) print "bad";
+        "#;
+        assert_eq!(run(source), "0\n1\n2\n");
+    }
+
+    #[test]
+    fn for_loop() {
+        let source = r#"
+            for (var i = 0; i < 3; i = i + 1) print i;
+
+            var a = 0;
+            var b = 1;
+            for (; a < 30; ) {
+              print a;
+              var temp = a;
+              a = b;
+              b = temp + b;
+              if (a > 10) a = 100;
+            }
+        "#;
+        assert_eq!(run(source), "0\n1\n2\n0\n1\n1\n2\n3\n5\n8\n");
+    }
+
+    #[test]
+    fn for_loop_variable_is_scoped_to_the_loop() {
+        let (printed, result) = try_run("for (var i = 0; i < 1; i = i + 1) print i; print i;");
+
+        assert_eq!(printed, "0\n");
+        assert_eq!(result.err().unwrap().to_string(), "Undefined variable 'i'.");
+    }
+
+    #[test]
+    fn logical_operators_return_an_operand() {
+        let source = r#"
+            print 1 and 2;
+            print nil and 2;
+            print false and nil;
+            print 1 or 2;
+            print nil or "default";
+            print false or nil;
+            print "" and "empty string is truthy";
+        "#;
+        assert_eq!(
+            run(source),
+            "2\nnil\nfalse\n1\ndefault\nnil\nempty string is truthy\n"
+        );
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        let source = r#"
+            var a = "unchanged";
+            false and (a = "and ran");
+            true or (a = "or ran");
+            print a;
+            true and (a = "and ran");
+            print a;
+            nil or (a = "or ran");
+            print a;
+            false and undefined;
+            true or undefined;
+        "#;
+        assert_eq!(run(source), "unchanged\nand ran\nor ran\n");
+    }
+
+    #[test]
+    fn functions_and_return() {
+        let source = r#"
+            fun add(a, b) { return a + b; }
+            print add(1, 2);
+
+            fun fib(n) {
+              if (n < 2) return n;
+              return fib(n - 1) + fib(n - 2);
+            }
+            print fib(15);
+
+            fun early() {
+              while (true) {
+                return "from loop";
+              }
+            }
+            print early();
+
+            fun nothing() { return; }
+            fun empty() {}
+            print nothing();
+            print empty();
+            print add;
+        "#;
+        assert_eq!(run(source), "3\n610\nfrom loop\nnil\nnil\n<fn add>\n");
+    }
+
+    #[test]
+    fn closures() {
+        let source = r#"
+            fun makeCounter() {
+              var i = 0;
+              fun count() {
+                i = i + 1;
+                return i;
+              }
+              return count;
+            }
+            var a = makeCounter();
+            var b = makeCounter();
+            a();
+            print a();
+            print b();
+        "#;
+        assert_eq!(run(source), "2\n1\n");
+    }
+
+    #[test]
+    fn closures_bind_the_variable_in_scope_where_they_are_declared() {
+        let source = r#"
+            var a = "global";
+            {
+              fun showA() {
+                print a;
+              }
+              showA();
+              var a = "block";
+              showA();
+            }
+        "#;
+        assert_eq!(run(source), "global\nglobal\n");
+    }
+
+    #[test]
+    fn native_clock() {
+        assert_eq!(
+            run("print clock; print clock() > 0;"),
+            "<native fn>\ntrue\n"
+        );
+    }
+
+    #[test]
+    fn call_errors() {
+        let (_, result) = try_run("fun f(a, b) {}\nf(1);");
+        let error = result.err().unwrap();
+        assert_eq!(error.to_string(), "Expected 2 arguments but got 1.");
+        assert_eq!(error.token().unwrap().line(), 2);
+
+        let (_, result) = try_run("clock(1);");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Expected 0 arguments but got 1."
+        );
+
+        let (_, result) = try_run("\"not a function\"();");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Can only call functions and classes."
+        );
+    }
+
+    #[test]
+    fn scope_is_restored_after_return() {
+        let source = r#"
+            var a = "global";
+            fun f() {
+              var a = "local";
+              { return a; }
+            }
+            print f();
+            print a;
+        "#;
+        assert_eq!(run(source), "local\nglobal\n");
+    }
+
+    #[test]
+    fn classes_and_instances() {
+        let source = r#"
+            class Bagel {}
+            print Bagel;
+            var bagel = Bagel();
+            print bagel;
+            bagel.topping = "sesame";
+            print bagel.topping;
+            print bagel.topping = "poppy";
+        "#;
+        assert_eq!(run(source), "Bagel\nBagel instance\nsesame\npoppy\n");
+    }
+
+    #[test]
+    fn methods_bind_this() {
+        let source = r#"
+            class Cake {
+              taste() {
+                var adjective = "delicious";
+                print "The " + this.flavor + " cake is " + adjective + "!";
+              }
+              flavor() { return this; }
+            }
+            var cake = Cake();
+            cake.flavor = "German chocolate";
+            cake.taste();
+
+            class Counter {
+              count() { this.n = this.n + 1; return this.n; }
+              callback() {
+                fun increment() { return this.count(); }
+                return increment;
+              }
+            }
+            var counter = Counter();
+            counter.n = 0;
+            var count = counter.count;
+            count();
+            print counter.callback()();
+        "#;
+        assert_eq!(run(source), "The German chocolate cake is delicious!\n2\n");
+    }
+
+    #[test]
+    fn initializers() {
+        let source = r#"
+            class Point {
+              init(x, y) {
+                this.x = x;
+                this.y = y;
+                if (x > 100) return;
+                this.small = true;
+              }
+            }
+            var p = Point(1, 2);
+            print p.x + p.y;
+            print p.small;
+            print p.init(3, 4).x;
+            print Point(200, 0).x;
+        "#;
+        assert_eq!(run(source), "3\ntrue\n3\n200\n");
+    }
+
+    #[test]
+    fn class_errors() {
+        let (_, result) = try_run("class Point { init(x) {} }\nPoint();");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Expected 1 arguments but got 0."
+        );
+
+        let (_, result) = try_run("class A {}\nprint A().missing;");
+        let error = result.err().unwrap();
+        assert_eq!(error.to_string(), "Undefined property 'missing'.");
+        assert_eq!(error.token().unwrap().line(), 2);
+
+        let (_, result) = try_run("var a = 1; a.b;");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Only instances have properties."
+        );
+
+        let (_, result) = try_run("\"str\".length = 3;");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Only instances have fields."
+        );
+    }
+
+    #[test]
+    fn inheritance() {
+        let source = r#"
+            class Doughnut {
+              init(filling) { this.filling = filling; }
+              cook() { print "Fry until golden brown."; }
+              describe() { return "Doughnut with " + this.filling; }
+            }
+            class BostonCream < Doughnut {
+              init() { super.init("custard"); }
+              cook() {
+                super.cook();
+                print "Pipe full of " + this.filling + ".";
+              }
+            }
+            var cream = BostonCream();
+            cream.cook();
+            print cream.describe();
+        "#;
+        assert_eq!(
+            run(source),
+            "Fry until golden brown.\nPipe full of custard.\nDoughnut with custard\n"
+        );
+    }
+
+    #[test]
+    fn super_binds_to_the_superclass_of_the_declaring_class() {
+        let source = r#"
+            class A { method() { print "A method"; } }
+            class B < A {
+              method() { print "B method"; }
+              test() { super.method(); }
+            }
+            class C < B {}
+            C().test();
+
+            var method = C().test;
+            method();
+        "#;
+        assert_eq!(run(source), "A method\nA method\n");
+    }
+
+    #[test]
+    fn inheritance_errors() {
+        let (_, result) =
+            try_run("var NotAClass = \"I am totally not a class\";\nclass Subclass < NotAClass {}");
+        let error = result.err().unwrap();
+        assert_eq!(error.to_string(), "Superclass must be a class.");
+        assert_eq!(error.token().unwrap().lexeme(), "NotAClass");
+        assert_eq!(error.token().unwrap().line(), 2);
+
+        let (_, result) = try_run("fun f() {}\nclass B < f {}");
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Superclass must be a class."
+        );
+
+        let (_, result) = try_run("class A {}\nclass B < A { m() { super.missing(); } }\nB().m();");
+        let error = result.err().unwrap();
+        assert_eq!(error.to_string(), "Undefined property 'missing'.");
+        assert_eq!(error.token().unwrap().line(), 2);
+    }
+
+    #[test]
+    fn break_and_continue() {
+        let source = r#"
+            var i = 0;
+            while (true) {
+              i = i + 1;
+              if (i > 3) break;
+              if (i < 2) continue;
+              print i;
+            }
+            for (var j = 0; j < 5; j = j + 1) {
+              if (j < 3) continue;
+              print j;
+            }
+            for (var k = 0; k < 3; k = k + 1) {
+              for (;;) { if (k > 0) break; print "inner"; break; }
+              if (k > 1) break;
+              print k;
+            }
+        "#;
+        assert_eq!(run(source), "2\n3\n3\n4\ninner\n0\n1\n");
+    }
+
+    #[test]
+    fn break_leaves_scopes_and_functions_alone() {
+        let source = r#"
+            var a = "outer";
+            while (true) {
+              var a = "inner";
+              { var b = 1; break; }
+            }
+            print a;
+
+            fun f() {
+              for (var i = 0; ; i = i + 1) {
+                if (i == 2) return i;
+              }
+            }
+            print f();
+        "#;
+        assert_eq!(run(source), "outer\n2\n");
+    }
 }
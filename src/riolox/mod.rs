@@ -1,30 +1,53 @@
 use std::rc::Rc;
 
 use self::{
+    error::{LuxError, ParseError, ScanError},
+    span::Span,
     token::{Token, TokenLiteral},
-    visitor::Visitor,
 };
 
+#[cfg(test)]
+use self::visitor::Visitor;
+
+#[cfg(test)]
+mod bench;
+pub(crate) mod callable;
+pub(crate) mod class;
+pub(crate) mod environment;
 pub(crate) mod error;
 pub(crate) mod interpreter;
+pub(crate) mod options;
 pub(crate) mod parser;
+// Only tests print syntax trees, to check what the parser built.
+#[cfg(test)]
 pub(crate) mod printers;
+pub(crate) mod resolver;
 pub(crate) mod scanner;
+pub(crate) mod span;
 pub(crate) mod token;
 pub(crate) mod visitor;
 
 pub(crate) type CompilerResult = Result<(), CompilationError>;
 
 pub(crate) enum CompilationError {
-    UndefinedError,
+    Scan(Vec<ScanError>),
+    Parse(Vec<ParseError>),
+    Runtime(LuxError),
 }
 
-pub fn error(line: usize, msg: &str) {
-    report(line, "", msg)
+pub(crate) fn error_at(span: &Span, msg: &str) {
+    println!(
+        "[line {}, column {}] Error: {}",
+        span.line, span.column, msg
+    )
 }
 
-pub fn report(line: usize, error: &str, reason: &str) {
-    println!("[line {}] Error {}: {}", line, error, reason)
+// Like `error_at`, for errors that have a stable code such as `P001`.
+pub(crate) fn coded_error_at(span: &Span, code: &str, msg: &str) {
+    println!(
+        "[line {}, column {}] Error {}: {}",
+        span.line, span.column, code, msg
+    )
 }
 
 #[derive(Debug)]
@@ -32,10 +55,66 @@ pub enum Expr {
     Literal(TokenLiteral), // Is it really?
     Unary(Token, Rc<Expr>),
     Binary(Rc<Expr>, Token, Rc<Expr>),
+    // `and` and `or`, which only evaluate the right operand when needed.
+    Logical(Rc<Expr>, Token, Rc<Expr>),
     Grouping(Rc<Expr>),
     Conditional(Rc<Expr>, Rc<Expr>, Rc<Expr>),
+    // String pieces and interpolated expressions, in source order.
+    Interpolation(Vec<Rc<Expr>>),
+    Variable(Token),
+    Assign(Token, Rc<Expr>),
+    // The callee, the closing parenthesis (for errors) and the arguments.
+    Call(Rc<Expr>, Token, Vec<Rc<Expr>>),
+    // `object.name`
+    Get(Rc<Expr>, Token),
+    // `object.name = value`
+    Set(Rc<Expr>, Token, Rc<Expr>),
+    This(Token),
+    // `super.method`, with the `super` keyword and the method name.
+    Super(Token, Token),
+}
+
+#[derive(Debug)]
+pub enum Stmt {
+    Expression(Rc<Expr>),
+    Print(Rc<Expr>),
+    // The variable's name, its initializer and its `///` doc comment, if any.
+    // Doc comments are kept for tooling, running the code doesn't need them.
+    Var(Token, Option<Rc<Expr>>, #[allow(dead_code)] Option<String>),
+    Block(Vec<Stmt>),
+    If(Rc<Expr>, Box<Stmt>, Option<Box<Stmt>>),
+    // The condition, the body and, for a desugared `for`, the increment. The
+    // increment is kept apart from the body so `continue` still runs it.
+    While(Rc<Expr>, Box<Stmt>, Option<Rc<Expr>>),
+    Function(Rc<FunctionDecl>),
+    Class(Rc<ClassDecl>),
+    // The `return` keyword (for errors) and the value, if any.
+    Return(Token, Option<Rc<Expr>>),
+    Break,
+    Continue,
+}
+
+// Shared between the AST and every function value created from it.
+#[derive(Debug)]
+pub struct FunctionDecl {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+    #[allow(dead_code)]
+    pub doc: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ClassDecl {
+    pub name: Token,
+    // An `Expr::Variable` naming the superclass.
+    pub superclass: Option<Rc<Expr>>,
+    pub methods: Vec<Rc<FunctionDecl>>,
+    #[allow(dead_code)]
+    pub doc: Option<String>,
 }
 
+#[cfg(test)]
 impl Expr {
     pub fn visit<V, R>(&self, v: &V) -> R
     where
@@ -0,0 +1,55 @@
+// Which rio-lox extensions on top of book Lox are switched on. Shared by the
+// scanner and the parser so both agree on the dialect being read.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) struct LanguageOptions {
+    // `a ? b : c`
+    pub ternary: bool,
+    // `a, b`
+    pub comma: bool,
+    // `"${a}"` in strings
+    pub interpolation: bool,
+    // `r"a\b"` and `r#"a "b" c"#`
+    pub raw_strings: bool,
+    // `"""` strings spanning lines, with their indentation removed
+    pub multiline_strings: bool,
+    // Keywords that are not in book Lox, see `TokenType::keyword`.
+    pub extra_keywords: bool,
+}
+
+impl LanguageOptions {
+    pub fn strict() -> Self {
+        LanguageOptions {
+            ternary: false,
+            comma: false,
+            interpolation: false,
+            raw_strings: false,
+            multiline_strings: false,
+            extra_keywords: false,
+        }
+    }
+
+    pub fn extended() -> Self {
+        LanguageOptions {
+            ternary: true,
+            comma: true,
+            interpolation: true,
+            raw_strings: true,
+            multiline_strings: true,
+            extra_keywords: true,
+        }
+    }
+
+    pub fn from_dialect(name: &str) -> Option<Self> {
+        match name {
+            "strict" => Some(Self::strict()),
+            "extended" => Some(Self::extended()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for LanguageOptions {
+    fn default() -> Self {
+        Self::extended()
+    }
+}
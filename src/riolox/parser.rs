@@ -2,24 +2,380 @@ use std::rc::Rc;
 
 use super::{
     error::ParseError,
+    options::LanguageOptions,
+    span::Span,
     token::{Token, TokenLiteral, TokenType},
-    Expr,
+    ClassDecl, Expr, FunctionDecl, Stmt,
 };
 
+// Calls and declarations take at most this many arguments or parameters.
+pub(crate) const MAX_ARGUMENTS: usize = 255;
+
 type ParseResult = Result<Rc<Expr>, ParseError>;
+type StmtResult = Result<Stmt, ParseError>;
 
-pub struct Parser {
-    tokens: Vec<Rc<Token>>,
+// Tokens are pulled from `source` only as the parser reaches them, so it can
+// run straight off a streaming `Scanner`.
+pub struct Parser<'a> {
+    tokens: Vec<Token>,
+    source: Box<dyn Iterator<Item = Token> + 'a>,
     current: usize,
+    options: LanguageOptions,
+    errors: Vec<ParseError>,
+    // Whether the parser is in a loop body, for checking `break` and `continue`.
+    in_loop: bool,
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Rc<Token>>) -> Parser {
-        Self { tokens, current: 0 }
+#[cfg(test)]
+impl Parser<'static> {
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Self::from_tokens(tokens)
+    }
+}
+
+impl<'a> Parser<'a> {
+    pub fn from_tokens<I>(tokens: I) -> Self
+    where
+        I: IntoIterator<Item = Token>,
+        I::IntoIter: 'a,
+    {
+        let mut parser = Parser {
+            tokens: vec![],
+            source: Box::new(tokens.into_iter()),
+            current: 0,
+            options: LanguageOptions::default(),
+            errors: vec![],
+            in_loop: false,
+        };
+        parser.pull();
+        parser
+    }
+
+    pub fn with_options(mut self, options: LanguageOptions) -> Self {
+        self.options = options;
+        self
     }
 
+    // A single expression, for tests. On an error there is nothing to return,
+    // the error is kept in `errors`.
+    #[cfg(test)]
     pub fn parse(&mut self) -> Option<Rc<Expr>> {
-        self.expression().ok()
+        match self.expression() {
+            Ok(expr) => Some(expr),
+            Err(error) => {
+                self.errors.push(error);
+                None
+            }
+        }
+    }
+
+    // Parses declarations up to `Eof`. A declaration with a syntax error is
+    // left out and parsing picks up again at the next statement, so `errors`
+    // ends up with every error in the source and the returned statements are
+    // the ones that parsed.
+    pub fn parse_program(&mut self) -> Vec<Stmt> {
+        let mut statements = vec![];
+
+        while !self.finished() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+
+        statements
+    }
+
+    #[cfg(test)]
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    pub fn into_errors(self) -> Vec<ParseError> {
+        self.errors
+    }
+
+    #[cfg(test)]
+    pub fn had_error(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    // Recovers from an error in the declaration by skipping to the start of
+    // the next statement, after keeping the error in `errors`.
+    fn declaration(&mut self) -> Option<Stmt> {
+        match self.declaration_or_error() {
+            Ok(statement) => Some(statement),
+            Err(error) => {
+                self.errors.push(error);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn declaration_or_error(&mut self) -> StmtResult {
+        // The scanner puts `///` comments on the declaration's first token.
+        let doc = self.peek().doc().map(str::to_owned);
+
+        if self.match_token(&[TokenType::Class]) {
+            return self.class_declaration(doc);
+        }
+
+        if self.match_token(&[TokenType::Fun]) {
+            return Ok(Stmt::Function(self.function("function", doc)?));
+        }
+
+        if self.match_token(&[TokenType::Var]) {
+            return self.var_declaration(doc);
+        }
+
+        self.statement()
+    }
+
+    fn class_declaration(&mut self, doc: Option<String>) -> StmtResult {
+        let name = self.consume(TokenType::Identifier, "Expected class name.")?;
+
+        let superclass = if self.match_token(&[TokenType::Less]) {
+            let superclass = self.consume(TokenType::Identifier, "Expected superclass name.")?;
+            Some(Rc::new(Expr::Variable(superclass)))
+        } else {
+            None
+        };
+
+        self.consume(TokenType::LeftBrace, "Expected '{' before class body.")?;
+
+        let methods = self.methods()?;
+
+        self.consume(TokenType::RightBrace, "Expected '}' after class body.")?;
+        Ok(Stmt::Class(Rc::new(ClassDecl {
+            name,
+            superclass,
+            methods,
+            doc,
+        })))
+    }
+
+    fn methods(&mut self) -> Result<Vec<Rc<FunctionDecl>>, ParseError> {
+        let mut methods = vec![];
+        while !self.check(&TokenType::RightBrace) && !self.finished() {
+            let doc = self.peek().doc().map(str::to_owned);
+            methods.push(self.function("method", doc)?);
+        }
+        Ok(methods)
+    }
+
+    // `kind` is "function" or "method", for the error messages.
+    fn function(
+        &mut self,
+        kind: &str,
+        doc: Option<String>,
+    ) -> Result<Rc<FunctionDecl>, ParseError> {
+        let name = self.consume(TokenType::Identifier, &format!("Expected {kind} name."))?;
+        self.consume(
+            TokenType::LeftParen,
+            &format!("Expected '(' after {kind} name."),
+        )?;
+
+        let mut params = vec![];
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    self.report(ParseError::TooManyParameters(self.peek().clone().into()));
+                }
+                params.push(self.consume(TokenType::Identifier, "Expected parameter name.")?);
+
+                if !self.match_token(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expected ')' after parameters.")?;
+        self.consume(
+            TokenType::LeftBrace,
+            &format!("Expected '{{' before {kind} body."),
+        )?;
+
+        // A loop around the declaration does not reach into the body.
+        let enclosing_loop = std::mem::replace(&mut self.in_loop, false);
+        let body = self.block();
+        self.in_loop = enclosing_loop;
+
+        Ok(Rc::new(FunctionDecl {
+            name,
+            params,
+            body: body?,
+            doc,
+        }))
+    }
+
+    fn var_declaration(&mut self, doc: Option<String>) -> StmtResult {
+        let name = self.consume(TokenType::Identifier, "Expected variable name.")?;
+
+        let initializer = if self.match_token(&[TokenType::Equal]) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        self.consume(
+            TokenType::Semicolon,
+            "Expected ';' after variable declaration.",
+        )?;
+        Ok(Stmt::Var(name, initializer, doc))
+    }
+
+    fn statement(&mut self) -> StmtResult {
+        if self.match_token(&[TokenType::If]) {
+            return self.if_statement();
+        }
+
+        if self.match_token(&[TokenType::While]) {
+            return self.while_statement();
+        }
+
+        if self.match_token(&[TokenType::For]) {
+            return self.for_statement();
+        }
+
+        if self.match_token(&[TokenType::Print]) {
+            return self.print_statement();
+        }
+
+        if self.match_token(&[TokenType::Return]) {
+            return self.return_statement();
+        }
+
+        if self.match_token(&[TokenType::Break, TokenType::Continue]) {
+            return self.loop_jump_statement();
+        }
+
+        if self.match_token(&[TokenType::LeftBrace]) {
+            return Ok(Stmt::Block(self.block()?));
+        }
+
+        self.expression_statement()
+    }
+
+    // The opening `{` is already consumed.
+    fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        let mut statements = vec![];
+
+        while !self.check(&TokenType::RightBrace) && !self.finished() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+
+        self.consume(TokenType::RightBrace, "Expected '}' after block.")?;
+        Ok(statements)
+    }
+
+    // An `else` belongs to the nearest `if` before it.
+    fn if_statement(&mut self) -> StmtResult {
+        self.consume(TokenType::LeftParen, "Expected '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expected ')' after if condition.")?;
+
+        let then_branch = Box::new(self.statement()?);
+        let else_branch = if self.match_token(&[TokenType::Else]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+
+        Ok(Stmt::If(condition, then_branch, else_branch))
+    }
+
+    fn while_statement(&mut self) -> StmtResult {
+        self.consume(TokenType::LeftParen, "Expected '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expected ')' after condition.")?;
+        let body = self.loop_body()?;
+
+        Ok(Stmt::While(condition, Box::new(body), None))
+    }
+
+    fn loop_body(&mut self) -> StmtResult {
+        let enclosing = std::mem::replace(&mut self.in_loop, true);
+        let body = self.statement();
+        self.in_loop = enclosing;
+        body
+    }
+
+    // There is no `for` statement in the AST, the loop is turned into
+    // `{ initializer; while (condition) body; }` with the increment attached
+    // to the `while`.
+    fn for_statement(&mut self) -> StmtResult {
+        self.consume(TokenType::LeftParen, "Expected '(' after 'for'.")?;
+
+        let initializer = if self.match_token(&[TokenType::Semicolon]) {
+            None
+        } else if self.match_token(&[TokenType::Var]) {
+            Some(self.var_declaration(None)?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if self.check(&TokenType::Semicolon) {
+            Rc::new(Expr::Literal(TokenLiteral::Bool(true)))
+        } else {
+            self.expression()?
+        };
+        self.consume(TokenType::Semicolon, "Expected ';' after loop condition.")?;
+
+        let increment = if self.check(&TokenType::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::RightParen, "Expected ')' after for clauses.")?;
+
+        let body = self.loop_body()?;
+        let mut body = Stmt::While(condition, Box::new(body), increment);
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(vec![initializer, body]);
+        }
+
+        Ok(body)
+    }
+
+    fn print_statement(&mut self) -> StmtResult {
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expected ';' after value.")?;
+        Ok(Stmt::Print(value))
+    }
+
+    fn return_statement(&mut self) -> StmtResult {
+        let keyword = self.previous().clone();
+        let value = if self.check(&TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::Semicolon, "Expected ';' after return value.")?;
+        Ok(Stmt::Return(keyword, value))
+    }
+
+    // `break;` or `continue;`, the keyword is already consumed.
+    fn loop_jump_statement(&mut self) -> StmtResult {
+        let keyword = self.previous().clone();
+        if !self.in_loop {
+            self.report(ParseError::OutsideLoop(keyword.clone().into()));
+        }
+        self.consume(
+            TokenType::Semicolon,
+            &format!("Expected ';' after '{}'.", keyword.lexeme()),
+        )?;
+
+        match keyword.t {
+            TokenType::Break => Ok(Stmt::Break),
+            _ => Ok(Stmt::Continue),
+        }
+    }
+
+    fn expression_statement(&mut self) -> StmtResult {
+        let expr = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expected ';' after expression.")?;
+        Ok(Stmt::Expression(expr))
     }
 
     fn expression(&mut self) -> ParseResult {
@@ -27,30 +383,80 @@ impl Parser {
     }
 
     fn comma_expression(&mut self) -> ParseResult {
-        let mut expr = self.ternary()?;
+        let mut expr = self.assignment()?;
 
         while self.match_token(&[TokenType::Comma]) {
-            let token = self.previous();
-            let right = self.equality()?;
-            expr = Rc::new(Expr::Binary(expr, token.as_ref().clone(), right));
+            let token = self.previous().clone();
+            if !self.options.comma {
+                return Err(ParseError::DisabledOperator(token.into()));
+            }
+            let right = self.assignment()?;
+            expr = Rc::new(Expr::Binary(expr, token, right));
+        }
+
+        Ok(expr)
+    }
+
+    // Assignment is right-associative. The target is parsed as an ordinary
+    // expression first and only then checked to be a variable.
+    fn assignment(&mut self) -> ParseResult {
+        let expr = self.ternary()?;
+
+        if self.match_token(&[TokenType::Equal]) {
+            let equals = self.previous().clone();
+            let value = self.assignment()?;
+
+            match expr.as_ref() {
+                Expr::Variable(name) => return Ok(Rc::new(Expr::Assign(name.clone(), value))),
+                Expr::Get(object, name) => {
+                    return Ok(Rc::new(Expr::Set(object.clone(), name.clone(), value)))
+                }
+                _ => {}
+            }
+
+            // The parser is not confused, so report without unwinding.
+            self.report(ParseError::InvalidAssignmentTarget(equals.into()));
         }
 
         Ok(expr)
     }
 
     fn ternary(&mut self) -> ParseResult {
-        let expr = self.equality()?;
+        let expr = self.or()?;
 
         if self.match_token(&[TokenType::QuestionMark]) {
-            let then_ex = self.equality()?;
-            if self.match_token(&[TokenType::Colon]) {
-                let else_ex = self.equality()?;
-                return Ok(Rc::new(Expr::Conditional(expr, then_ex, else_ex)));
+            if !self.options.ternary {
+                return Err(ParseError::DisabledOperator(self.previous().clone().into()));
             }
-            return Err(ParseError::Generic(
-                Rc::new(self.previous().as_ref().clone()),
-                "Colon exptected.".to_owned(),
-            ));
+            let then_ex = self.or()?;
+            self.consume(
+                TokenType::Colon,
+                "Expected ':' after then branch of conditional expression.",
+            )?;
+            let else_ex = self.or()?;
+            return Ok(Rc::new(Expr::Conditional(expr, then_ex, else_ex)));
+        }
+
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> ParseResult {
+        let mut expr = self.and()?;
+
+        while self.match_token(&[TokenType::Or]) {
+            let operator = self.previous().clone();
+            expr = Rc::new(Expr::Logical(expr, operator, self.and()?))
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> ParseResult {
+        let mut expr = self.equality()?;
+
+        while self.match_token(&[TokenType::And]) {
+            let operator = self.previous().clone();
+            expr = Rc::new(Expr::Logical(expr, operator, self.equality()?))
         }
 
         Ok(expr)
@@ -60,12 +466,8 @@ impl Parser {
         let mut expr = self.comparison()?;
 
         while self.match_token(&[TokenType::EqualEqual, TokenType::BangEqual]) {
-            let prev_token = self.previous();
-            expr = Rc::new(Expr::Binary(
-                expr,
-                prev_token.as_ref().clone(),
-                self.comparison()?,
-            ))
+            let prev_token = self.previous().clone();
+            expr = Rc::new(Expr::Binary(expr, prev_token, self.comparison()?))
         }
 
         Ok(expr)
@@ -80,13 +482,9 @@ impl Parser {
             TokenType::Less,
             TokenType::LessEqual,
         ]) {
-            let prev_token = self.previous();
+            let prev_token = self.previous().clone();
 
-            expr = Rc::new(Expr::Binary(
-                expr,
-                prev_token.as_ref().clone(),
-                self.term()?,
-            ));
+            expr = Rc::new(Expr::Binary(expr, prev_token, self.term()?));
         }
 
         Ok(expr)
@@ -96,13 +494,9 @@ impl Parser {
         let mut expr = self.factor()?;
 
         while self.match_token(&[TokenType::Plus, TokenType::Minus]) {
-            let prev_token = self.previous();
+            let prev_token = self.previous().clone();
 
-            expr = Rc::new(Expr::Binary(
-                expr,
-                prev_token.as_ref().clone(),
-                self.factor()?,
-            ));
+            expr = Rc::new(Expr::Binary(expr, prev_token, self.factor()?));
         }
 
         Ok(expr)
@@ -112,13 +506,9 @@ impl Parser {
         let mut expr = self.unary()?;
 
         while self.match_token(&[TokenType::Star, TokenType::Slash]) {
-            let prev_token = self.previous();
+            let prev_token = self.previous().clone();
 
-            expr = Rc::new(Expr::Binary(
-                expr,
-                prev_token.as_ref().clone(),
-                self.unary()?,
-            ));
+            expr = Rc::new(Expr::Binary(expr, prev_token, self.unary()?));
         }
 
         Ok(expr)
@@ -126,14 +516,51 @@ impl Parser {
 
     fn unary(&mut self) -> ParseResult {
         if self.match_token(&[TokenType::Bang, TokenType::Minus]) {
-            let previous = self.previous();
-            return Ok(Rc::new(Expr::Unary(
-                previous.as_ref().clone(),
-                self.unary()?,
-            )));
+            let previous = self.previous().clone();
+            return Ok(Rc::new(Expr::Unary(previous, self.unary()?)));
+        }
+
+        self.call()
+    }
+
+    fn call(&mut self) -> ParseResult {
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.match_token(&[TokenType::LeftParen]) {
+                expr = self.finish_call(expr)?;
+            } else if self.match_token(&[TokenType::Dot]) {
+                let name =
+                    self.consume(TokenType::Identifier, "Expected property name after '.'.")?;
+                expr = Rc::new(Expr::Get(expr, name));
+            } else {
+                break;
+            }
         }
 
-        self.primary()
+        Ok(expr)
+    }
+
+    // Arguments are parsed below the comma operator, so `f(a, b)` passes two
+    // arguments rather than one comma expression.
+    fn finish_call(&mut self, callee: Rc<Expr>) -> ParseResult {
+        let mut arguments = vec![];
+
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    self.report(ParseError::TooManyArguments(self.peek().clone().into()));
+                }
+                arguments.push(self.assignment()?);
+
+                if !self.match_token(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self.consume(TokenType::RightParen, "Expected ')' after arguments.")?;
+        Ok(Rc::new(Expr::Call(callee, paren, arguments)))
     }
 
     fn primary(&mut self) -> ParseResult {
@@ -145,37 +572,47 @@ impl Parser {
             return Ok(Rc::new(Expr::Literal(TokenLiteral::Bool(true))));
         }
 
-        if self.match_token(&[TokenType::Number]) {
-            let previous = self.previous();
-            return Ok(Rc::new(Expr::Literal(previous.literal().clone())));
+        if self.match_token(&[TokenType::Nil]) {
+            return Ok(Rc::new(Expr::Literal(TokenLiteral::Nil)));
         }
 
-        if self.match_token(&[TokenType::String]) {
-            let previous = self.previous();
-            return Ok(Rc::new(Expr::Literal(previous.literal().clone())));
+        if self.match_token(&[TokenType::Number, TokenType::String]) {
+            let literal = self.previous().literal().cloned();
+            return Ok(Rc::new(Expr::Literal(
+                literal.expect("the scanner gives strings and numbers a value"),
+            )));
         }
 
-        if self.match_token(&[TokenType::LeftParen]) {
-            let expr = self.expression()?;
+        if self.match_token(&[TokenType::Identifier]) {
+            return Ok(Rc::new(Expr::Variable(self.previous().clone())));
+        }
 
-            if self.match_token(&[TokenType::RightParen]) {
-                return Ok(Rc::new(Expr::Grouping(expr)));
-            }
+        if self.match_token(&[TokenType::This]) {
+            return Ok(Rc::new(Expr::This(self.previous().clone())));
+        }
 
-            // Change to consume I guess?
-            return Self::error(ParseError::Generic(
-                self.previous(),
-                "Expected ')' after expression".to_owned(),
-            ));
+        if self.match_token(&[TokenType::Super]) {
+            let keyword = self.previous().clone();
+            self.consume(TokenType::Dot, "Expected '.' after 'super'.")?;
+            let method = self.consume(TokenType::Identifier, "Expected superclass method name.")?;
+            return Ok(Rc::new(Expr::Super(keyword, method)));
+        }
+
+        if self.match_token(&[TokenType::Interpolation]) {
+            return self.interpolation();
+        }
+
+        if self.match_token(&[TokenType::LeftParen]) {
+            let expr = self.expression()?;
+            self.consume(TokenType::RightParen, "Expected ')' after expression.")?;
+            return Ok(Rc::new(Expr::Grouping(expr)));
         }
 
         if self.match_token(&[TokenType::BangEqual, TokenType::EqualEqual]) {
-            let prev = self.previous();
-            let _ = self.equality();
-            return Self::error(ParseError::Generic(
-                prev,
-                "Expected left hand operand".to_owned(),
+            self.report(ParseError::MissingLeftOperand(
+                self.previous().clone().into(),
             ));
+            return self.equality();
         }
 
         if self.match_token(&[
@@ -184,46 +621,80 @@ impl Parser {
             TokenType::Greater,
             TokenType::GreaterEqual,
         ]) {
-            let prev = self.previous();
-            let _ = self.comparison();
-            return Self::error(ParseError::Generic(
-                prev,
-                "Expected left hand operand".to_owned(),
+            self.report(ParseError::MissingLeftOperand(
+                self.previous().clone().into(),
             ));
+            return self.comparison();
         }
 
         if self.match_token(&[TokenType::Plus]) {
-            let prev = self.previous();
-            let _ = self.term();
-            return Self::error(ParseError::Generic(
-                prev,
-                "Expected left hand operand".to_owned(),
+            self.report(ParseError::MissingLeftOperand(
+                self.previous().clone().into(),
             ));
+            return self.term();
         }
 
         if self.match_token(&[TokenType::Slash, TokenType::Star]) {
-            let prev = self.previous();
-            let _ = self.factor();
-            return Self::error(ParseError::Generic(
-                prev,
-                "Expected left hand operand".to_owned(),
+            self.report(ParseError::MissingLeftOperand(
+                self.previous().clone().into(),
             ));
+            return self.factor();
+        }
+
+        Err(ParseError::ExpectedExpression(self.peek().clone().into()))
+    }
+
+    fn interpolation(&mut self) -> ParseResult {
+        let mut parts = vec![];
+
+        loop {
+            let segment = self.previous();
+            if segment.literal() != Some(&TokenLiteral::from("")) {
+                parts.push(Rc::new(Expr::Literal(
+                    segment
+                        .literal()
+                        .cloned()
+                        .expect("the scanner gives segments a value"),
+                )));
+            }
+
+            if segment.token_type() == &TokenType::String {
+                return Ok(Rc::new(Expr::Interpolation(parts)));
+            }
+
+            parts.push(self.expression()?);
+
+            if !self.match_token(&[TokenType::Interpolation, TokenType::String]) {
+                return Err(ParseError::ExpectedToken {
+                    expected: TokenType::RightBrace,
+                    found: self.peek().clone().into(),
+                    message: "Expected '}' after interpolated expression.".to_owned(),
+                });
+            }
+        }
+    }
+
+    fn consume(&mut self, t: TokenType, msg: &str) -> Result<Token, ParseError> {
+        if self.check(&t) {
+            self.advance();
+            return Ok(self.previous().clone());
         }
 
-        Self::error(ParseError::Generic(
-            self.peek().clone().into(),
-            "Expected expression.".to_owned(),
-        ))
+        Err(ParseError::ExpectedToken {
+            expected: t,
+            found: self.peek().clone().into(),
+            message: msg.to_owned(),
+        })
     }
 
-    fn error(error: ParseError) -> ParseResult {
-        let token = error.token();
-        super::error(token.line(), &error.to_string());
-        Err(error)
+    fn report(&mut self, error: ParseError) {
+        self.errors.push(error);
     }
 
-    fn previous(&self) -> Rc<Token> {
-        self.tokens[self.current - 1].clone()
+    // Borrowed, callers clone it when the syntax tree keeps the token. A clone
+    // only bumps reference counts, see `Token`.
+    fn previous(&self) -> &Token {
+        &self.tokens[self.current - 1]
     }
 
     fn match_token(&mut self, types: &[TokenType]) -> bool {
@@ -257,7 +728,9 @@ impl Parser {
                 | TokenType::For
                 | TokenType::If
                 | TokenType::While
-                | TokenType::Print => return,
+                | TokenType::Print
+                | TokenType::Break
+                | TokenType::Continue => return,
                 _ => {}
             }
 
@@ -267,11 +740,37 @@ impl Parser {
 
     fn advance(&mut self) -> &Token {
         if !self.finished() {
-            self.current += 1
+            self.current += 1;
+            self.pull();
         }
         &self.tokens[self.current]
     }
 
+    // Makes sure `tokens[current]` exists. A source that runs dry without an
+    // `Eof` gets one made up at the position of its last token.
+    fn pull(&mut self) {
+        if self.current < self.tokens.len() {
+            return;
+        }
+
+        let token = self.source.next().unwrap_or_else(|| {
+            let span = self
+                .tokens
+                .last()
+                .map(|token| Span {
+                    start: token.span().end,
+                    ..*token.span()
+                })
+                .unwrap_or_default();
+            Token::new(TokenType::Eof, "", span)
+        });
+        self.tokens.push(token);
+    }
+
+    fn check(&self, t: &TokenType) -> bool {
+        self.peek().token_type() == t
+    }
+
     fn peek(&self) -> &Token {
         &self.tokens[self.current]
     }
@@ -280,3 +779,546 @@ impl Parser {
         self.tokens[self.current].token_type() == &TokenType::Eof
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::riolox::{printers::AstPrinter, scanner::Scanner, span::FileId};
+
+    use super::*;
+
+    #[test]
+    fn parses_from_streaming_scanner() {
+        let scanner = Scanner::from_reader(Cursor::new("1 +\n\"${2 *\n3}\"".to_owned()));
+        let mut parser = Parser::from_tokens(scanner);
+
+        let expr = parser.parse().unwrap();
+
+        assert_eq!(AstPrinter {}.print(&expr), "(+ 1 (interpolate (* 2 3)))");
+    }
+
+    #[test]
+    fn missing_eof_is_made_up() {
+        let mut scanner = Scanner::new("12".to_owned());
+        let tokens = scanner.scan_tokens()[..1].to_vec();
+        let mut parser = Parser::new(tokens);
+
+        assert!(parser.parse().is_some());
+        assert!(parser.finished());
+        assert_eq!(parser.peek().span(), &Span::new(FileId(0), 2, 2, 1, 1));
+    }
+
+    fn parse(source: &str, options: LanguageOptions) -> Option<String> {
+        let mut scanner = Scanner::new(source.to_owned()).with_options(options);
+        let tokens = scanner.scan_tokens().to_vec();
+        let mut parser = Parser::new(tokens).with_options(options);
+
+        parser.parse().map(|expr| AstPrinter {}.print(&expr))
+    }
+
+    #[test]
+    fn extended_dialect_allows_ternary_and_comma() {
+        let options = LanguageOptions::extended();
+
+        assert_eq!(parse("1 ? 2 : 3", options).unwrap(), "(? 1 2 3)");
+        assert_eq!(parse("1, 2", options).unwrap(), "(, 1 2)");
+    }
+
+    #[test]
+    fn strict_dialect_rejects_ternary_and_comma() {
+        let options = LanguageOptions::strict();
+
+        assert_eq!(parse("1 ? 2 : 3", options), None);
+        assert_eq!(parse("1, 2", options), None);
+        assert_eq!(
+            parse("(1 + 2) * 3", options).unwrap(),
+            "(* (group (+ 1 2)) 3)"
+        );
+    }
+
+    fn parse_program(source: &str) -> (Vec<Stmt>, Vec<String>) {
+        let mut scanner = Scanner::new(source.to_owned());
+        let mut parser = Parser::new(scanner.scan_tokens().to_vec());
+
+        let program = parser.parse_program();
+        let errors = parser.errors().iter().map(|e| e.to_string()).collect();
+        (program, errors)
+    }
+
+    #[test]
+    fn program_statements() {
+        let (program, errors) = parse_program("var a = 1 + 2; var b; print \"hi\"; 3 * 4;");
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        let printer = AstPrinter {};
+        match program.as_slice() {
+            [Stmt::Var(a, Some(init), _), Stmt::Var(b, None, _), Stmt::Print(value), Stmt::Expression(expr)] =>
+            {
+                assert_eq!(a.lexeme(), "a");
+                assert_eq!(printer.print(init), "(+ 1 2)");
+                assert_eq!(b.lexeme(), "b");
+                assert_eq!(printer.print(value), "hi");
+                assert_eq!(printer.print(expr), "(* 3 4)");
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn program_stops_at_error() {
+        let (program, errors) = parse_program("print 1; print 2 var c = 3;");
+
+        assert_eq!(program.len(), 1);
+        assert_eq!(errors, vec!["Expected ';' after value."]);
+
+        let (program, errors) = parse_program("var 1 = 2;");
+        assert!(program.is_empty());
+        assert_eq!(errors, vec!["Expected variable name."]);
+    }
+
+    #[test]
+    fn assignment() {
+        let options = LanguageOptions::default();
+
+        assert_eq!(parse("a = b = 1", options).unwrap(), "(= a (= b 1))");
+        assert_eq!(parse("a = 1 ? b : c", options).unwrap(), "(= a (? 1 b c))");
+        assert_eq!(
+            parse("a = 1, b = 2", options).unwrap(),
+            "(, (= a 1) (= b 2))"
+        );
+    }
+
+    #[test]
+    fn invalid_assignment_target() {
+        let (program, errors) = parse_program("a + b = c; 1 = 2; (a) = 3; a = 4;");
+
+        assert_eq!(program.len(), 4);
+        assert_eq!(errors, vec!["Invalid assignment target."; 3]);
+    }
+
+    #[test]
+    fn blocks() {
+        let (program, errors) = parse_program("{ var a = 1; { print a; } } {}");
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        match program.as_slice() {
+            [Stmt::Block(outer), Stmt::Block(empty)] => {
+                assert!(
+                    matches!(outer.as_slice(), [Stmt::Var(..), Stmt::Block(inner)] if inner.len() == 1)
+                );
+                assert!(empty.is_empty());
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn unclosed_block() {
+        let (program, errors) = parse_program("{ print 1;");
+
+        assert!(program.is_empty());
+        assert_eq!(errors, vec!["Expected '}' after block."]);
+    }
+
+    #[test]
+    fn for_is_desugared_to_while() {
+        let (program, errors) = parse_program("for (var i = 0; i < 3; i = i + 1) print i;");
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        let printer = AstPrinter {};
+        match program.as_slice() {
+            [Stmt::Block(outer)] => match outer.as_slice() {
+                [Stmt::Var(name, Some(_), _), Stmt::While(condition, body, Some(increment))] => {
+                    assert_eq!(name.lexeme(), "i");
+                    assert_eq!(printer.print(condition), "(< i 3)");
+                    assert!(matches!(body.as_ref(), Stmt::Print(_)));
+                    assert_eq!(printer.print(increment), "(= i (+ i 1))");
+                }
+                other => panic!("{:?}", other),
+            },
+            other => panic!("{:?}", other),
+        }
+
+        let (program, _) = parse_program("for (;;) {}");
+        match program.as_slice() {
+            [Stmt::While(condition, body, None)] => {
+                assert_eq!(printer.print(condition), "true");
+                assert!(matches!(body.as_ref(), Stmt::Block(body) if body.is_empty()));
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn else_binds_to_nearest_if() {
+        let (program, _) = parse_program("if (a) if (b) c; else d;");
+
+        match program.as_slice() {
+            [Stmt::If(_, inner, None)] => {
+                assert!(matches!(inner.as_ref(), Stmt::If(_, _, Some(_))))
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn control_flow_errors() {
+        let (_, errors) = parse_program("if a) print 1;");
+        assert_eq!(errors, vec!["Expected '(' after 'if'."]);
+
+        let (_, errors) = parse_program("while (true print 1;");
+        assert_eq!(errors, vec!["Expected ')' after condition."]);
+
+        let (_, errors) = parse_program("for (var i = 0; i < 1 i = i + 1) {}");
+        assert_eq!(errors, vec!["Expected ';' after loop condition."]);
+    }
+
+    #[test]
+    fn logical_operator_precedence() {
+        let options = LanguageOptions::default();
+
+        assert_eq!(parse("a or b and c", options).unwrap(), "(or a (and b c))");
+        assert_eq!(parse("a and b or c", options).unwrap(), "(or (and a b) c)");
+        assert_eq!(parse("a == b and c", options).unwrap(), "(and (== a b) c)");
+        assert_eq!(
+            parse("a or b ? c : d or e", options).unwrap(),
+            "(? (or a b) c (or d e))"
+        );
+        assert_eq!(parse("x = a or b", options).unwrap(), "(= x (or a b))");
+        assert_eq!(
+            parse("a and b", LanguageOptions::strict()).unwrap(),
+            "(and a b)"
+        );
+    }
+
+    #[test]
+    fn calls() {
+        let options = LanguageOptions::default();
+
+        assert_eq!(parse("f()", options).unwrap(), "(call f)");
+        assert_eq!(
+            parse("f(1, a = 2)(3)", options).unwrap(),
+            "(call (call f 1 (= a 2)) 3)"
+        );
+        assert_eq!(parse("-f(1) * 2", options).unwrap(), "(* (- (call f 1)) 2)");
+    }
+
+    #[test]
+    fn function_declarations() {
+        let (program, errors) =
+            parse_program("fun add(a, b) { return a + b; } fun f() { return; }");
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        match program.as_slice() {
+            [Stmt::Function(add), Stmt::Function(f)] => {
+                assert_eq!(add.name.lexeme(), "add");
+                let params: Vec<&str> = add.params.iter().map(|p| p.lexeme()).collect();
+                assert_eq!(params, vec!["a", "b"]);
+                assert!(matches!(add.body.as_slice(), [Stmt::Return(_, Some(_))]));
+                assert!(f.params.is_empty());
+                assert!(matches!(f.body.as_slice(), [Stmt::Return(_, None)]));
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn argument_and_parameter_limits() {
+        let names = |n: usize| {
+            (0..n)
+                .map(|i| format!("a{i}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let (_, errors) = parse_program(&format!("f({});", names(255)));
+        assert!(errors.is_empty(), "{:?}", errors);
+        let (program, errors) = parse_program(&format!("f({});", names(256)));
+        assert_eq!(program.len(), 1);
+        assert_eq!(errors, vec!["Can't have more than 255 arguments."]);
+
+        let (_, errors) = parse_program(&format!("fun f({}) {{}}", names(255)));
+        assert!(errors.is_empty(), "{:?}", errors);
+        let (_, errors) = parse_program(&format!("fun f({}) {{}}", names(256)));
+        assert_eq!(errors, vec!["Can't have more than 255 parameters."]);
+    }
+
+    #[test]
+    fn property_access() {
+        let options = LanguageOptions::default();
+
+        assert_eq!(parse("a.b.c", options).unwrap(), "(. (. a b) c)");
+        assert_eq!(
+            parse("a.b(1).c = 2", options).unwrap(),
+            "(= (. (call (. a b) 1) c) 2)"
+        );
+        assert_eq!(
+            parse("a.b = c.d = 1", options).unwrap(),
+            "(= (. a b) (= (. c d) 1))"
+        );
+    }
+
+    #[test]
+    fn class_declarations() {
+        let (program, errors) =
+            parse_program("class A { init(x) { this.x = x; } get() { return this.x; } }");
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        match program.as_slice() {
+            [Stmt::Class(class)] => {
+                assert_eq!(class.name.lexeme(), "A");
+                let methods: Vec<&str> = class.methods.iter().map(|m| m.name.lexeme()).collect();
+                assert_eq!(methods, vec!["init", "get"]);
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn doc_comments_on_declarations() {
+        let source = "/// Counts.\nvar n = 0;\n/// Adds.\n/// Twice.\nfun add(a, b) {}\n\
+                      /// A point.\nclass Point {\n  /// Makes one.\n  init() {}\n  plain() {}\n}\n\
+                      var undocumented;";
+        let (program, errors) = parse_program(source);
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        match program.as_slice() {
+            [Stmt::Var(_, _, n), Stmt::Function(add), Stmt::Class(point), Stmt::Var(_, _, none)] => {
+                assert_eq!(n.as_deref(), Some("Counts."));
+                assert_eq!(add.doc.as_deref(), Some("Adds.\nTwice."));
+                assert_eq!(point.doc.as_deref(), Some("A point."));
+                assert_eq!(point.methods[0].doc.as_deref(), Some("Makes one."));
+                assert_eq!(point.methods[1].doc, None);
+                assert_eq!(*none, None);
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn subclasses() {
+        let (program, errors) = parse_program("class B < A { m() { return super.m; } }");
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        match program.as_slice() {
+            [Stmt::Class(class)] => {
+                assert_eq!(class.name.lexeme(), "B");
+                match class.superclass.as_deref() {
+                    Some(Expr::Variable(name)) => assert_eq!(name.lexeme(), "A"),
+                    other => panic!("{:?}", other),
+                }
+                match class.methods[0].body.as_slice() {
+                    [Stmt::Return(_, Some(value))] => {
+                        assert_eq!(AstPrinter {}.print(value), "(. super m)")
+                    }
+                    other => panic!("{:?}", other),
+                }
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn super_syntax_errors() {
+        let (_, errors) = parse_program("class B < A { m() { super; } }");
+        assert_eq!(errors, vec!["Expected '.' after 'super'."]);
+
+        let (_, errors) = parse_program("class B < A { m() { super.1; } }");
+        assert_eq!(errors, vec!["Expected superclass method name."]);
+    }
+
+    #[test]
+    fn break_and_continue() {
+        let (program, errors) =
+            parse_program("while (a) { if (b) break; continue; } for (;;) { { break; } }");
+
+        assert!(errors.is_empty(), "{:?}", errors);
+        match program.as_slice() {
+            [Stmt::While(_, body, None), Stmt::While(..)] => match body.as_ref() {
+                Stmt::Block(body) => match body.as_slice() {
+                    [Stmt::If(_, then_branch, None), Stmt::Continue] => {
+                        assert!(matches!(then_branch.as_ref(), Stmt::Break));
+                    }
+                    other => panic!("{:?}", other),
+                },
+                other => panic!("{:?}", other),
+            },
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn break_and_continue_outside_loops() {
+        let (_, errors) = parse_program("break; { continue; } if (a) break;");
+        assert_eq!(
+            errors,
+            vec![
+                "Can't use 'break' outside of a loop.",
+                "Can't use 'continue' outside of a loop.",
+                "Can't use 'break' outside of a loop.",
+            ]
+        );
+
+        let (_, errors) = parse_program("while (a) { fun f() { break; } }");
+        assert_eq!(errors, vec!["Can't use 'break' outside of a loop."]);
+
+        let (_, errors) = parse_program("while (a) { fun f() { while (b) continue; } break; }");
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let (_, errors) = parse_program("while (a) break");
+        assert_eq!(errors, vec!["Expected ';' after 'break'."]);
+    }
+
+    #[test]
+    fn reports_every_syntax_error() {
+        let source = "var a = ;\nprint 1;\nvar = 2;\nprint (3;\nprint 4;";
+        let (program, errors) = parse_program(source);
+
+        assert_eq!(
+            errors,
+            vec![
+                "Expected expression.",
+                "Expected variable name.",
+                "Expected ')' after expression.",
+            ]
+        );
+        let printer = AstPrinter {};
+        let printed: Vec<String> = program
+            .iter()
+            .map(|statement| match statement {
+                Stmt::Print(value) => printer.print(value),
+                other => panic!("{:?}", other),
+            })
+            .collect();
+        assert_eq!(printed, vec!["1", "4"]);
+    }
+
+    #[test]
+    fn recovers_inside_blocks() {
+        let source = "fun f() {\n  var x = 1 +;\n  print x;\n}\nwhile (true) { 1 2; break; }";
+        let (program, errors) = parse_program(source);
+
+        assert_eq!(
+            errors,
+            vec!["Expected expression.", "Expected ';' after expression."]
+        );
+        match program.as_slice() {
+            [Stmt::Function(f), Stmt::While(_, body, None)] => {
+                assert!(matches!(f.body.as_slice(), [Stmt::Print(_)]));
+                match body.as_ref() {
+                    Stmt::Block(body) => assert!(matches!(body.as_slice(), [Stmt::Break])),
+                    other => panic!("{:?}", other),
+                }
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    fn parse_errors(source: &str) -> Vec<ParseError> {
+        let mut scanner = Scanner::new(source.to_owned());
+        let mut parser = Parser::new(scanner.scan_tokens().to_vec());
+
+        parser.parse_program();
+        parser.into_errors()
+    }
+
+    #[test]
+    fn expected_token_errors() {
+        let errors = parse_errors("print 1 2;\nvar x = (1;");
+
+        match errors.as_slice() {
+            [ParseError::ExpectedToken {
+                expected: TokenType::Semicolon,
+                found: first,
+                ..
+            }, ParseError::ExpectedToken {
+                expected: TokenType::RightParen,
+                found: second,
+                ..
+            }] => {
+                assert_eq!(first.lexeme(), "2");
+                assert_eq!(first.line(), 1);
+                assert_eq!(second.line(), 2);
+                assert_eq!(second.lexeme(), ";");
+            }
+            other => panic!(
+                "{:?}",
+                other.iter().map(|e| e.to_string()).collect::<Vec<_>>()
+            ),
+        }
+        assert!(errors.iter().all(|error| error.code() == "P001"));
+
+        let errors = parse_errors("1 ? 2;");
+        assert!(matches!(
+            errors.as_slice(),
+            [ParseError::ExpectedToken {
+                expected: TokenType::Colon,
+                ..
+            }]
+        ));
+        assert_eq!(
+            errors[0].to_string(),
+            "Expected ':' after then branch of conditional expression."
+        );
+    }
+
+    #[test]
+    fn errors_in_operand_without_left_operand() {
+        let errors = parse_errors("== (1;\nprint + 2;");
+
+        let codes: Vec<&str> = errors.iter().map(|error| error.code()).collect();
+        assert_eq!(codes, vec!["P003", "P001", "P003"]);
+        assert_eq!(errors[1].token().lexeme(), ";");
+        assert_eq!(errors[2].token().line(), 2);
+    }
+
+    #[test]
+    fn error_kinds_and_codes() {
+        let arguments = vec!["1"; MAX_ARGUMENTS + 1].join(", ");
+        let source = format!("* 2; 1 = 2; f({arguments}); break; print ;");
+        let errors = parse_errors(&source);
+
+        let codes: Vec<&str> = errors.iter().map(|error| error.code()).collect();
+        assert_eq!(codes, vec!["P003", "P004", "P005", "P014", "P002"]);
+        assert!(matches!(errors[0], ParseError::MissingLeftOperand(_)));
+        assert!(matches!(errors[1], ParseError::InvalidAssignmentTarget(_)));
+        assert_eq!(errors[1].token().lexeme(), "=");
+        assert!(matches!(errors[2], ParseError::TooManyArguments(_)));
+        assert!(matches!(errors[4], ParseError::ExpectedExpression(_)));
+
+        let parameters = (0..=MAX_ARGUMENTS)
+            .map(|i| format!("p{i}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let errors = parse_errors(&format!("fun f({parameters}) {{}}"));
+        assert!(matches!(
+            errors.as_slice(),
+            [ParseError::TooManyParameters(_)]
+        ));
+        assert_eq!(errors[0].token().lexeme(), format!("p{MAX_ARGUMENTS}"));
+    }
+
+    #[test]
+    fn disabled_operator_errors() {
+        let mut scanner = Scanner::new("1, 2 ? 3 : 4".to_owned());
+        let mut parser =
+            Parser::new(scanner.scan_tokens().to_vec()).with_options(LanguageOptions::strict());
+
+        assert!(parser.parse().is_none());
+        match parser.errors() {
+            [error @ ParseError::DisabledOperator(token)] => {
+                assert_eq!(token.lexeme(), ",");
+                assert_eq!(error.code(), "P007");
+                assert_eq!(
+                    error.to_string(),
+                    "The comma operator is not enabled in this dialect."
+                );
+            }
+            other => panic!(
+                "{:?}",
+                other.iter().map(|e| e.to_string()).collect::<Vec<_>>()
+            ),
+        }
+    }
+}
@@ -23,38 +23,57 @@ impl Visitor<String> for AstPrinter {
         match expr {
             Expr::Literal(val) => val.to_string(),
             Expr::Unary(token, expr) => self.parenthesize(token.lexeme(), vec![expr.as_ref()]),
-            Expr::Binary(left, op, right) => self.parenthesize(
-                &op.literal().to_string(),
-                vec![left.as_ref(), right.as_ref()],
-            ),
+            Expr::Binary(left, op, right) | Expr::Logical(left, op, right) => {
+                self.parenthesize(op.lexeme(), vec![left.as_ref(), right.as_ref()])
+            }
             Expr::Grouping(expr) => self.parenthesize("group", vec![expr.as_ref()]),
             Expr::Conditional(if_ex, then_ex, else_ex) => {
                 self.parenthesize("?", vec![if_ex, then_ex, else_ex])
             }
+            Expr::Interpolation(parts) => {
+                self.parenthesize("interpolate", parts.iter().map(|p| p.as_ref()).collect())
+            }
+            Expr::Variable(name) => name.lexeme().to_owned(),
+            Expr::This(_) => "this".to_owned(),
+            Expr::Super(_, method) => format!("(. super {})", method.lexeme()),
+            Expr::Get(object, name) => format!("(. {} {})", object.visit(self), name.lexeme()),
+            Expr::Set(object, name, value) => format!(
+                "(= (. {} {}) {})",
+                object.visit(self),
+                name.lexeme(),
+                value.visit(self)
+            ),
+            Expr::Call(callee, _, arguments) => self.parenthesize(
+                "call",
+                std::iter::once(callee)
+                    .chain(arguments)
+                    .map(|e| e.as_ref())
+                    .collect(),
+            ),
+            Expr::Assign(name, value) => {
+                self.parenthesize(&format!("= {}", name.lexeme()), vec![value.as_ref()])
+            }
         }
     }
-
-    fn visit_mut(&mut self, expr: &Expr) -> String {
-        self.visit(expr)
-    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::rc::Rc;
 
-    use tests::{
-        parser::Expr,
+    use crate::riolox::{
+        parser::Parser,
+        scanner::Scanner,
+        span::Span,
         token::{Token, TokenLiteral, TokenType},
+        Expr,
     };
 
-    use crate::riolox::*;
-
     use super::*;
 
     #[test]
     fn test_first() {
-        let minus = Token::new(TokenType::Minus, "-".to_owned(), "-", 1);
+        let minus = Token::new(TokenType::Minus, "-", Span::default());
         let unary = Rc::new(Expr::Unary(
             minus,
             Rc::new(Expr::Literal(TokenLiteral::from("123"))),
@@ -64,7 +83,7 @@ mod tests {
         )))));
         let expr = Expr::Binary(
             unary,
-            Token::new(TokenType::Star, "*".to_owned(), "*", 1),
+            Token::new(TokenType::Star, "*", Span::default()),
             grouping,
         );
 
@@ -73,4 +92,14 @@ mod tests {
 
         assert_eq!("(* (- 123) (group 45.67))", result)
     }
+
+    #[test]
+    fn scanned_operators() {
+        let mut scanner = Scanner::new("!true == -1 * 2".to_owned());
+        let mut parser = Parser::new(scanner.scan_tokens().to_vec());
+
+        let expr = parser.parse().unwrap();
+
+        assert_eq!("(== (! true) (* (- 1) 2))", AstPrinter {}.print(&expr));
+    }
 }
@@ -0,0 +1,364 @@
+use std::{collections::HashMap, rc::Rc};
+
+use super::{
+    error::ParseError, interpreter::Interpreter, token::Token, ClassDecl, Expr, FunctionDecl, Stmt,
+};
+
+// What kind of function body the resolver is in, for checking `return`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum FunctionKind {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+// Whether the resolver is in a class body, for checking `this` and `super`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum ClassKind {
+    None,
+    Class,
+    Subclass,
+}
+
+// Runs between the parser and the interpreter. It works out which scope each
+// local variable refers to and tells the interpreter, so a closure keeps
+// seeing the variables that were in scope where it was written. Globals are
+// left out and looked up by name when the code runs.
+//
+// It also reports what the grammar allows but the language doesn't: `return`
+// outside a function or with a value in `init`, `this` outside a class,
+// `super` outside a subclass, a class inheriting from itself, and a local
+// declared twice in one scope or read in its own initializer. `break` and
+// `continue` outside a loop are caught by the parser.
+pub(crate) struct Resolver<'a> {
+    interpreter: &'a mut Interpreter,
+    // Innermost last. Each name maps to whether its initializer has run.
+    scopes: Vec<HashMap<String, bool>>,
+    function: FunctionKind,
+    class: ClassKind,
+    errors: Vec<ParseError>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(interpreter: &'a mut Interpreter) -> Self {
+        Resolver {
+            interpreter,
+            scopes: vec![],
+            function: FunctionKind::None,
+            class: ClassKind::None,
+            errors: vec![],
+        }
+    }
+
+    // Returns the errors in source order. A program with errors should not
+    // be run.
+    pub fn resolve(mut self, statements: &[Stmt]) -> Vec<ParseError> {
+        self.statements(statements);
+        self.errors
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expression(expr) | Stmt::Print(expr) => self.expression(expr),
+            Stmt::Var(name, initializer, _) => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.expression(initializer);
+                }
+                self.define(name.lexeme());
+            }
+            Stmt::Block(statements) => {
+                self.scopes.push(HashMap::new());
+                self.statements(statements);
+                self.scopes.pop();
+            }
+            Stmt::If(condition, then_branch, else_branch) => {
+                self.expression(condition);
+                self.statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            Stmt::While(condition, body, increment) => {
+                self.expression(condition);
+                self.statement(body);
+                if let Some(increment) = increment {
+                    self.expression(increment);
+                }
+            }
+            Stmt::Function(declaration) => {
+                // Defined before the body so the function can call itself.
+                self.declare(&declaration.name);
+                self.define(declaration.name.lexeme());
+                self.function(declaration, FunctionKind::Function);
+            }
+            Stmt::Class(declaration) => self.class(declaration),
+            Stmt::Return(keyword, value) => {
+                if self.function == FunctionKind::None {
+                    self.errors
+                        .push(ParseError::ReturnOutsideFunction(keyword.clone().into()));
+                }
+                if let Some(value) = value {
+                    // `init` always returns `this`, a bare `return;` is fine.
+                    if self.function == FunctionKind::Initializer {
+                        self.errors.push(ParseError::ReturnValueFromInitializer(
+                            keyword.clone().into(),
+                        ));
+                    }
+                    self.expression(value);
+                }
+            }
+            Stmt::Break | Stmt::Continue => {}
+        }
+    }
+
+    // The scopes match the environments the interpreter makes: `super` around
+    // the methods of a subclass, `this` around each bound method, then the
+    // parameters, which the body shares.
+    fn class(&mut self, declaration: &ClassDecl) {
+        self.declare(&declaration.name);
+        self.define(declaration.name.lexeme());
+
+        let enclosing = std::mem::replace(&mut self.class, ClassKind::Class);
+        if let Some(superclass) = &declaration.superclass {
+            if let Expr::Variable(name) = superclass.as_ref() {
+                if name.lexeme() == declaration.name.lexeme() {
+                    self.errors
+                        .push(ParseError::InheritFromSelf(name.clone().into()));
+                }
+            }
+            self.class = ClassKind::Subclass;
+            self.expression(superclass);
+
+            self.scopes.push(HashMap::new());
+            self.define("super");
+        }
+
+        self.scopes.push(HashMap::new());
+        self.define("this");
+        for method in &declaration.methods {
+            let kind = match method.name.lexeme() {
+                "init" => FunctionKind::Initializer,
+                _ => FunctionKind::Method,
+            };
+            self.function(method, kind);
+        }
+        self.scopes.pop();
+
+        if declaration.superclass.is_some() {
+            self.scopes.pop();
+        }
+        self.class = enclosing;
+    }
+
+    fn function(&mut self, declaration: &FunctionDecl, kind: FunctionKind) {
+        let enclosing = std::mem::replace(&mut self.function, kind);
+        self.scopes.push(HashMap::new());
+        for param in &declaration.params {
+            self.declare(param);
+            self.define(param.lexeme());
+        }
+        self.statements(&declaration.body);
+        self.scopes.pop();
+        self.function = enclosing;
+    }
+
+    fn expression(&mut self, expr: &Rc<Expr>) {
+        match expr.as_ref() {
+            Expr::Literal(_) => {}
+            Expr::Unary(_, operand) | Expr::Grouping(operand) => self.expression(operand),
+            Expr::Binary(left, _, right) | Expr::Logical(left, _, right) => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expr::Conditional(condition, then_branch, else_branch) => {
+                self.expression(condition);
+                self.expression(then_branch);
+                self.expression(else_branch);
+            }
+            Expr::Interpolation(parts) => {
+                for part in parts {
+                    self.expression(part);
+                }
+            }
+            Expr::Variable(name) => {
+                let scope = self.scopes.last();
+                if scope.and_then(|scope| scope.get(name.lexeme())) == Some(&false) {
+                    self.errors
+                        .push(ParseError::OwnInitializer(name.clone().into()));
+                }
+                self.resolve_local(expr, name.lexeme());
+            }
+            Expr::Assign(name, value) => {
+                self.expression(value);
+                self.resolve_local(expr, name.lexeme());
+            }
+            Expr::Call(callee, _, arguments) => {
+                self.expression(callee);
+                for argument in arguments {
+                    self.expression(argument);
+                }
+            }
+            Expr::Get(object, _) => self.expression(object),
+            Expr::Set(object, _, value) => {
+                self.expression(object);
+                self.expression(value);
+            }
+            Expr::This(keyword) => {
+                if self.class == ClassKind::None {
+                    self.errors
+                        .push(ParseError::ThisOutsideClass(keyword.clone().into()));
+                    return;
+                }
+                self.resolve_local(expr, "this");
+            }
+            Expr::Super(keyword, _) => match self.class {
+                ClassKind::None => self
+                    .errors
+                    .push(ParseError::SuperOutsideClass(keyword.clone().into())),
+                ClassKind::Class => self
+                    .errors
+                    .push(ParseError::SuperWithoutSuperclass(keyword.clone().into())),
+                ClassKind::Subclass => self.resolve_local(expr, "super"),
+            },
+        }
+    }
+
+    // Declared but not yet usable, until `define`.
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        if scope.contains_key(name.lexeme()) {
+            self.errors
+                .push(ParseError::AlreadyDeclared(name.clone().into()));
+        }
+        scope.insert(name.lexeme().to_owned(), false);
+    }
+
+    fn define(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_owned(), true);
+        }
+    }
+
+    // Names that are in no scope are globals, and not resolved.
+    fn resolve_local(&mut self, expr: &Rc<Expr>, name: &str) {
+        let depth = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(name));
+        if let Some(depth) = depth {
+            self.interpreter.resolve(expr, depth);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::riolox::{parser::Parser, scanner::Scanner};
+
+    use super::*;
+
+    fn resolve_errors(source: &str) -> Vec<ParseError> {
+        let mut scanner = Scanner::new(source.to_owned());
+        let mut parser = Parser::new(scanner.scan_tokens().to_vec());
+        let program = parser.parse_program();
+        assert!(!parser.had_error(), "{}", source);
+
+        Resolver::new(&mut Interpreter::new()).resolve(&program)
+    }
+
+    fn messages(source: &str) -> Vec<String> {
+        resolve_errors(source)
+            .iter()
+            .map(|error| error.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn return_outside_function() {
+        assert_eq!(
+            messages("return 1;"),
+            vec!["Can't return from top-level code."]
+        );
+        assert_eq!(
+            messages("fun f() { fun g() {} return 1; } { return; }"),
+            vec!["Can't return from top-level code."]
+        );
+    }
+
+    #[test]
+    fn class_static_errors() {
+        assert_eq!(
+            messages("class A { init() { return 1; } }"),
+            vec!["Can't return a value from an initializer."]
+        );
+        assert!(messages("class A { init() { fun f() { return 1; } return; } }").is_empty());
+        assert_eq!(
+            messages("print this; fun f() { this.x; }"),
+            vec!["Can't use 'this' outside of a class."; 2]
+        );
+        assert!(messages("class A { m() { fun f() { return this; } } }").is_empty());
+        assert!(messages("class A { m() {} } fun init() { return 1; }").is_empty());
+    }
+
+    #[test]
+    fn inheritance_static_errors() {
+        assert_eq!(
+            messages("class A < A {}"),
+            vec!["A class can't inherit from itself."]
+        );
+        assert_eq!(
+            messages("super.m(); fun f() { super.m; }"),
+            vec!["Can't use 'super' outside of a class."; 2]
+        );
+        assert_eq!(
+            messages("class A { m() { super.m(); } }"),
+            vec!["Can't use 'super' in a class with no superclass."]
+        );
+        assert_eq!(
+            messages("class B < A { m() { class C { n() { super.n; } } } }"),
+            vec!["Can't use 'super' in a class with no superclass."]
+        );
+    }
+
+    #[test]
+    fn local_declarations() {
+        assert_eq!(
+            messages("{ var a = 1; var a = 2; } fun f(b, b) {}"),
+            vec!["Already a variable with this name in this scope."; 2]
+        );
+        assert_eq!(
+            messages("{ var a = a; }"),
+            vec!["Can't read local variable in its own initializer."]
+        );
+        assert!(messages("var a = 1; var a = a; { var b = a; { var a = b; } }").is_empty());
+    }
+
+    #[test]
+    fn error_kinds_and_codes() {
+        let errors = resolve_errors(
+            "return; print this; super.m; \
+             class A < A { m() { super.m; } } class B { init() { return 1; } } \
+             { var a; var a; var b = b; }",
+        );
+
+        let codes: Vec<&str> = errors.iter().map(|error| error.code()).collect();
+        assert_eq!(
+            codes,
+            vec!["P008", "P010", "P011", "P013", "P009", "P015", "P016"]
+        );
+        assert_eq!(errors[3].token().lexeme(), "A");
+        assert!(matches!(errors[6], ParseError::OwnInitializer(_)));
+        assert_eq!(errors[6].token().lexeme(), "b");
+    }
+}
@@ -1,44 +1,241 @@
-use std::rc::Rc;
+use std::{io::BufRead, rc::Rc};
+
+use unicode_xid::UnicodeXID;
 
 use super::{
-    error,
-    token::{Token, TokenLiteral, TokenType},
+    error::ScanError,
+    options::LanguageOptions,
+    span::{FileId, Span},
+    token::{Lexeme, Token, TokenLiteral, TokenType, Trivia, TriviaKind},
 };
 
+// An open `${` inside a string. `braces` counts the `{` opened within the
+// interpolated expression, so we know which `}` resumes the string.
+struct Interpolation {
+    braces: usize,
+    span: Span,
+}
+
+// `start` and `current` are byte offsets into `source` and always sit on a
+// char boundary, so every lookup is a slice rather than a walk from the front.
+// When reading from `input`, `source` only holds what has not been turned into
+// tokens yet and `offset` is where it starts in the whole input. Lexemes
+// point into `shared`, a frozen copy of `source` that is only made again when
+// a lexeme is needed after `source` grew.
 pub(crate) struct Scanner {
-    // TODO: Use char0
     source: String,
-    tokens: Vec<Rc<Token>>,
+    shared: Rc<str>,
+    input: Option<Box<dyn BufRead>>,
+    offset: usize,
+    file: FileId,
+    tokens: Vec<Token>,
     start: usize,
     current: usize,
     line: usize,
+    column: usize,
+    start_line: usize,
+    start_column: usize,
+    interpolations: Vec<Interpolation>,
+    doc: Vec<String>,
+    lossless: bool,
+    trivia: Vec<Trivia>,
+    errors: Vec<ScanError>,
+    depth: usize,
+    finished: bool,
+    options: LanguageOptions,
 }
 
 impl Scanner {
     pub fn new(source: String) -> Self {
+        Self::with_file(source, FileId::default())
+    }
+
+    pub fn with_file(source: String, file: FileId) -> Self {
         Scanner {
             source,
+            shared: Rc::from(""),
+            input: None,
+            offset: 0,
+            file,
             tokens: vec![],
             start: 0,
             current: 0,
             line: 1,
+            column: 1,
+            start_line: 1,
+            start_column: 1,
+            interpolations: vec![],
+            doc: vec![],
+            lossless: false,
+            trivia: vec![],
+            errors: vec![],
+            depth: 0,
+            finished: false,
+            options: LanguageOptions::default(),
         }
     }
 
-    pub fn scan_tokens(&mut self) -> &[Rc<Token>] {
-        while self.has_more() {
-            self.start = self.current;
-            self.scan_token(); // This will probably need to be aligned...
-        }
+    // Reads the source line by line as tokens are asked for, see the
+    // `Iterator` impl.
+    pub fn from_reader<R: BufRead + 'static>(input: R) -> Self {
+        let mut scanner = Self::new(String::new());
+        scanner.input = Some(Box::new(input));
+        scanner
+    }
 
-        self.tokens.push(Rc::new(Token::new(
-            TokenType::Eof,
-            "".to_string(),
-            "",
-            self.line,
-        )));
+    pub fn with_options(mut self, options: LanguageOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    // Keeps whitespace, comments and anything skipped over as trivia on the
+    // tokens, so that their `full_text` adds back up to the source. For
+    // tooling, the interpreter has no use for trivia.
+    #[allow(dead_code)]
+    pub fn lossless(mut self) -> Self {
+        self.lossless = true;
+        self
+    }
+
+    pub fn scan_tokens(&mut self) -> &[Token] {
+        while self.scan_next() {}
         self.tokens.as_ref()
     }
+
+    // True when the input stopped inside a string, block comment,
+    // interpolation or unclosed bracket, i.e. a REPL should read another line
+    // and scan again rather than report the errors.
+    pub fn needs_more_input(&self) -> bool {
+        self.depth > 0
+            || self.errors.iter().any(|error| {
+                matches!(
+                    error,
+                    ScanError::UnterminatedString(_)
+                        | ScanError::UnterminatedBlockComment(_)
+                        | ScanError::UnterminatedInterpolation(_)
+                )
+            })
+    }
+
+    // Scans until at least one more token is produced. Returns false once
+    // `Eof` has been produced.
+    fn scan_next(&mut self) -> bool {
+        if self.finished {
+            return false;
+        }
+
+        let count = self.tokens.len();
+        while self.tokens.len() == count {
+            if self.at_end() {
+                self.finish();
+                break;
+            }
+
+            self.mark_start();
+            self.scan_token();
+            if self.lossless && self.tokens.len() == count {
+                self.push_trivia();
+            }
+        }
+        true
+    }
+
+    fn finish(&mut self) {
+        if let Some(interpolation) = self.interpolations.pop() {
+            self.report(ScanError::UnterminatedInterpolation(interpolation.span));
+            self.interpolations.clear();
+        }
+
+        self.mark_start();
+        let span = self.span();
+        let token = Token::new(TokenType::Eof, self.lexeme(), span);
+        self.add_token(token);
+        self.finished = true;
+    }
+
+    // Only called between tokens, so when reading from `input` everything
+    // scanned so far can be dropped before asking for more.
+    fn at_end(&mut self) -> bool {
+        if self.has_more() {
+            return false;
+        }
+        if self.input.is_some() {
+            self.offset += self.source.len();
+            self.source.clear();
+            self.shared = Rc::from("");
+            self.start = 0;
+            self.current = 0;
+        }
+        !self.fill()
+    }
+
+    // Appends the next line of `input` to the source.
+    fn fill(&mut self) -> bool {
+        let Some(input) = self.input.as_mut() else {
+            return false;
+        };
+
+        match input.read_line(&mut self.source) {
+            Ok(0) => {
+                self.input = None;
+                false
+            }
+            Ok(_) => true,
+            Err(e) => {
+                self.input = None;
+                let span = self.span_since((self.current, self.line, self.column));
+                self.report(ScanError::Read(span, e.to_string()));
+                false
+            }
+        }
+    }
+
+    // Problems found by `scan_tokens`. Scanning carries on past them, so the
+    // tokens are still usable for diagnostics but should not be run.
+    pub fn errors(&self) -> &[ScanError] {
+        &self.errors
+    }
+
+    pub fn had_error(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    fn report(&mut self, error: ScanError) {
+        self.errors.push(error);
+    }
+
+    fn mark_start(&mut self) {
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column;
+    }
+
+    fn newline(&mut self) {
+        self.line += 1;
+        self.column = 1;
+    }
+
+    fn span(&self) -> Span {
+        Span::new(
+            self.file,
+            self.offset + self.start,
+            self.offset + self.current,
+            self.start_line,
+            self.start_column,
+        )
+    }
+
+    // Span from an earlier (offset, line, column) position up to `current`.
+    fn span_since(&self, (start, line, column): (usize, usize, usize)) -> Span {
+        Span::new(
+            self.file,
+            self.offset + start,
+            self.offset + self.current,
+            line,
+            column,
+        )
+    }
+
     fn has_more(&self) -> bool {
         self.current < self.source.len()
     }
@@ -51,17 +248,35 @@ impl Scanner {
                 '?' => self.push_token(TokenType::QuestionMark),
                 ':' => self.push_token(TokenType::Colon),
                 '(' => {
+                    self.depth += 1;
                     self.push_token(TokenType::LeftParen);
                 }
                 ')' => {
+                    self.depth = self.depth.saturating_sub(1);
                     self.push_token(TokenType::RightParen);
                 }
                 '{' => {
+                    if let Some(interpolation) = self.interpolations.last_mut() {
+                        interpolation.braces += 1;
+                    }
+                    self.depth += 1;
                     self.push_token(TokenType::LeftBrace);
                 }
-                '}' => {
-                    self.push_token(TokenType::RightBrace);
-                }
+                '}' => match self.interpolations.last_mut() {
+                    Some(interpolation) if interpolation.braces == 0 => {
+                        self.interpolations.pop();
+                        self.string();
+                    }
+                    Some(interpolation) => {
+                        interpolation.braces -= 1;
+                        self.depth = self.depth.saturating_sub(1);
+                        self.push_token(TokenType::RightBrace);
+                    }
+                    None => {
+                        self.depth = self.depth.saturating_sub(1);
+                        self.push_token(TokenType::RightBrace);
+                    }
+                },
                 ',' => {
                     self.push_token(TokenType::Comma);
                 }
@@ -98,93 +313,153 @@ impl Scanner {
                 }
                 '/' => {
                     if self.is_next('/') {
-                        self.current += 1;
-
-                        let mut next = self.peek();
-                        while next.is_some() && next != Some('\n') {
-                            self.advance();
-                            next = self.peek();
-                        }
+                        self.advance();
+                        self.line_comment();
+                    } else if self.is_next('*') {
+                        self.advance();
+                        self.block_comment();
                     } else {
                         self.push_token(TokenType::Slash);
                     }
                 }
-                '\n' => {
-                    self.line += 1;
-                }
+                '\n' => self.newline(),
                 ' ' | '\r' | '\t' => {}
-                '"' => self.string(),
+                '"' => {
+                    if self.options.multiline_strings
+                        && self.is_next('"')
+                        && self.peek_at(1, |c| c == '"')
+                    {
+                        self.advance();
+                        self.advance();
+                        self.multiline_string();
+                    } else {
+                        self.string();
+                    }
+                }
                 _ => {
-                    if c.is_numeric() {
+                    if c.is_ascii_digit() {
                         self.number();
-                    } else if c.is_alphabetic() {
+                    } else if c == 'r' && self.options.raw_strings && self.raw_string_ahead() {
+                        self.raw_string();
+                    } else if c == '_' || c.is_xid_start() {
                         self.identifier();
                     } else {
-                        error(self.line, "Unexpected character.")
+                        self.report(ScanError::UnexpectedCharacter(self.span(), c))
                     }
                 }
             }
         }
     }
 
-    // TODO: Optimize this
     fn advance(&mut self) -> Option<char> {
-        let c = self.source.chars().nth(self.current);
-        self.current += 1;
-        c
+        if !self.has_more() {
+            self.fill();
+        }
+        let c = self.peek()?;
+        self.current += c.len_utf8();
+        self.column += 1;
+        Some(c)
     }
 
     fn push_token(&mut self, t: TokenType) {
-        // TODO: What if we assume that it's only ASCII?
-        let text = self.substring(self.start, self.current);
-        self.tokens
-            .push(Rc::new(Token::new(t, text, "", self.line)));
+        let span = self.span();
+        let token = Token::new(t, self.lexeme(), span);
+        self.add_token(token);
     }
 
     fn push_token_with_str(&mut self, t: TokenType, value: String) {
-        self.tokens.push(Rc::new(Token::with_value(
-            t,
-            TokenLiteral::Str(value),
-            "",
-            self.line,
-        )));
+        let span = self.span();
+        let token = Token::with_value(t, TokenLiteral::Str(value), self.lexeme(), span);
+        self.add_token(token);
     }
 
     fn push_token_with_num(&mut self, t: TokenType, value: f64) {
-        self.tokens.push(Rc::new(Token::with_value(
-            t,
-            TokenLiteral::Num(value),
-            "",
-            self.line,
-        )));
+        let span = self.span();
+        let token = Token::with_value(t, TokenLiteral::Num(value), self.lexeme(), span);
+        self.add_token(token);
     }
 
-    fn substring(&self, start: usize, end: usize) -> String {
-        self.source.chars().skip(start).take(end - start).collect()
+    fn lexeme(&mut self) -> Lexeme {
+        Lexeme::new(self.shared_source(), self.start, self.current)
     }
 
-    fn peek(&self) -> Option<char> {
-        if !self.has_more() {
-            return None;
+    // `source` only ever grows between resets, so a length mismatch means
+    // `shared` is stale.
+    fn shared_source(&mut self) -> Rc<str> {
+        if self.shared.len() != self.source.len() {
+            self.shared = Rc::from(self.source.as_str());
         }
-        self.char_at(self.current)
+        self.shared.clone()
     }
 
-    fn peek_char(&self, expected: char) -> bool {
-        self.peek_at(self.current, |ch| ch == expected)
+    // Doc comments collected since the last token belong to this one.
+    fn add_token(&mut self, mut token: Token) {
+        if !self.doc.is_empty() {
+            token.set_doc(self.doc.join("\n"));
+            self.doc.clear();
+        }
+        if self.lossless {
+            self.attach_trivia(&mut token);
+        }
+        self.tokens.push(token);
     }
 
-    fn char_at(&self, n: usize) -> Option<char> {
-        if n >= self.source.len() {
-            return None;
+    fn push_trivia(&mut self) {
+        let kind = TriviaKind::from(&self.source[self.start..self.current]);
+        let shared = self.shared_source();
+
+        if let Some(last) = self.trivia.last_mut() {
+            if kind == TriviaKind::Whitespace
+                && last.kind == kind
+                && last.text.shares_source(&shared)
+            {
+                last.text.extend_to(self.current);
+                last.span.end = self.offset + self.current;
+                return;
+            }
         }
-        self.source.chars().nth(n)
+
+        let text = self.lexeme();
+        let span = self.span();
+        self.trivia.push(Trivia { kind, text, span });
+    }
+
+    // Pending trivia up to the first newline trails the previous token, the
+    // rest leads `token`.
+    fn attach_trivia(&mut self, token: &mut Token) {
+        let newline = self
+            .trivia
+            .iter()
+            .position(|t| t.kind == TriviaKind::Newline)
+            .unwrap_or(self.trivia.len());
+
+        let leading = match self.tokens.last_mut() {
+            Some(previous) => {
+                let leading = self.trivia.split_off(newline);
+                previous.set_trailing_trivia(std::mem::take(&mut self.trivia));
+                leading
+            }
+            None => std::mem::take(&mut self.trivia),
+        };
+        token.set_leading_trivia(leading);
+    }
+
+    fn substring(&self, start: usize, end: usize) -> String {
+        self.source[start..end].to_string()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source[self.current..].chars().next()
+    }
+
+    fn peek_char(&self, expected: char) -> bool {
+        self.peek_at(0, |ch| ch == expected)
     }
 
     // TODO: Change to match. Return bool?
     fn on_next(&mut self, expected: char, on_true: TokenType, on_false: TokenType) -> TokenType {
         if self.is_next(expected) {
-            self.current += 1;
+            self.advance();
             on_true
         } else {
             on_false
@@ -192,82 +467,449 @@ impl Scanner {
     }
 
     fn is_next(&self, expected: char) -> bool {
-        if !self.has_more() {
-            return false;
+        self.peek_char(expected)
+    }
+
+    // The leading `//` is already consumed. `///` (but not `////`) makes it a
+    // doc comment, kept for the next token.
+    fn line_comment(&mut self) {
+        let is_doc = self.is_next('/') && !self.peek_at(1, |c| c == '/');
+        if is_doc {
+            self.advance();
+            if self.is_next(' ') {
+                self.advance();
+            }
         }
 
-        if let Some(ch) = self.char_at(self.current) {
-            ch == expected
-        } else {
-            false
+        let text_start = self.current;
+        let mut next = self.peek();
+        while next.is_some() && next != Some('\n') {
+            self.advance();
+            next = self.peek();
+        }
+
+        if is_doc {
+            let text = self.substring(text_start, self.current);
+            self.doc.push(text.trim_end_matches('\r').to_owned());
+        }
+    }
+
+    // The opening `/*` is already consumed. Block comments nest.
+    fn block_comment(&mut self) {
+        let mut depth = 1;
+
+        while depth > 0 {
+            match self.advance() {
+                None => {
+                    self.report(ScanError::UnterminatedBlockComment(self.span()));
+                    return;
+                }
+                Some('/') if self.is_next('*') => {
+                    self.advance();
+                    depth += 1;
+                }
+                Some('*') if self.is_next('/') => {
+                    self.advance();
+                    depth -= 1;
+                }
+                Some('\n') => self.newline(),
+                Some(_) => {}
+            }
         }
     }
 
+    // Scans a string up to its closing quote, or up to the next `${`, in which
+    // case the part so far becomes an `Interpolation` token and the string is
+    // picked up again by the `}` closing the interpolated expression.
     fn string(&mut self) {
-        let mut peek = self.peek();
-        while peek.is_some() && peek != Some('"') {
-            if peek == Some('\n') {
-                self.line += 1;
+        let mut value = String::new();
+
+        loop {
+            match self.advance() {
+                None => {
+                    self.report(ScanError::UnterminatedString(self.span()));
+                    return;
+                }
+                Some('"') => break,
+                Some('$') if self.options.interpolation && self.is_next('{') => {
+                    self.advance();
+                    let span = self.span_since((self.current - 2, self.line, self.column - 2));
+                    self.interpolations.push(Interpolation { braces: 0, span });
+                    self.push_token_with_str(TokenType::Interpolation, value);
+                    return;
+                }
+                Some('\\') => {
+                    if let Some(c) = self.escape() {
+                        value.push(c);
+                    }
+                }
+                Some('\n') => {
+                    self.newline();
+                    value.push('\n');
+                }
+                Some(c) => value.push(c),
             }
+        }
+
+        self.push_token_with_str(TokenType::String, value)
+    }
+
+    // Whether the `r` just consumed opens a raw string, i.e. is followed by
+    // any number of `#` and a quote.
+    fn raw_string_ahead(&self) -> bool {
+        let hashes = self.source[self.current..]
+            .chars()
+            .take_while(|&c| c == '#')
+            .count();
+        self.peek_at(hashes, |c| c == '"')
+    }
+
+    // The leading `r` is already consumed. Nothing in a raw string is escaped or
+    // interpolated, it ends at the first quote followed by as many `#` as it
+    // was opened with.
+    fn raw_string(&mut self) {
+        let mut hashes = 0;
+        while self.is_next('#') {
             self.advance();
-            peek = self.peek();
+            hashes += 1;
         }
+        self.advance();
+        let value_start = self.current;
 
-        if !self.has_more() {
-            error(self.line, "Unterminated string");
-            return;
+        loop {
+            match self.advance() {
+                None => {
+                    self.report(ScanError::UnterminatedString(self.span()));
+                    return;
+                }
+                Some('"') if (0..hashes).all(|i| self.peek_at(i, |c| c == '#')) => {
+                    let value = self.substring(value_start, self.current - 1);
+                    for _ in 0..hashes {
+                        self.advance();
+                    }
+                    self.push_token_with_str(TokenType::String, value);
+                    return;
+                }
+                Some('\n') => self.newline(),
+                Some(_) => {}
+            }
+        }
+    }
+
+    // The opening `"""` is already consumed. Escapes work as in other
+    // strings but `${` is kept as text. See `StringLine::join` for how the
+    // lines are put together.
+    fn multiline_string(&mut self) {
+        let mut lines = vec![StringLine::default()];
+
+        loop {
+            match self.advance() {
+                None => {
+                    self.report(ScanError::UnterminatedString(self.span()));
+                    return;
+                }
+                Some('"') if self.is_next('"') && self.peek_at(1, |c| c == '"') => {
+                    self.advance();
+                    self.advance();
+                    break;
+                }
+                Some('\\') => {
+                    if let Some(c) = self.escape() {
+                        lines.last_mut().unwrap().text.push(c);
+                    }
+                }
+                Some('\r') if self.is_next('\n') => {}
+                Some('\n') => {
+                    self.newline();
+                    lines.push(StringLine::default());
+                }
+                Some(c) => {
+                    let line = lines.last_mut().unwrap();
+                    if line.text.is_empty() && (c == ' ' || c == '\t') {
+                        line.indent.push(c);
+                    } else {
+                        line.text.push(c);
+                    }
+                }
+            }
+        }
+
+        self.push_token_with_str(TokenType::String, StringLine::join(lines))
+    }
+
+    // Decodes the escape after a backslash. Bad escapes are reported and dropped
+    // from the value, the string itself keeps scanning.
+    fn escape(&mut self) -> Option<char> {
+        let start = (self.current - 1, self.line, self.column - 1);
+
+        match self.advance()? {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '"' => Some('"'),
+            '\\' => Some('\\'),
+            '0' => Some('\0'),
+            '$' => Some('$'),
+            'u' => match self.unicode_escape() {
+                Ok(c) => Some(c),
+                Err(msg) => {
+                    self.report(ScanError::InvalidUnicodeEscape(self.span_since(start), msg));
+                    None
+                }
+            },
+            c => {
+                self.report(ScanError::UnknownEscape(self.span_since(start), c));
+                if c == '\n' {
+                    self.newline();
+                }
+                None
+            }
         }
+    }
 
-        // Closing "
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        if !self.is_next('{') {
+            return Err("Expected '{' after '\\u'.".to_owned());
+        }
         self.advance();
 
-        let value = self.substring(self.start + 1, self.current - 1);
-        self.push_token_with_str(TokenType::String, value)
+        let digits_start = self.current;
+        while self.peek_at(0, |c| c.is_ascii_hexdigit()) {
+            self.advance();
+        }
+        let digits_end = self.current;
+
+        if !self.is_next('}') {
+            return Err("Expected '}' to close unicode escape.".to_owned());
+        }
+        self.advance();
+        let digits = &self.source[digits_start..digits_end];
+
+        if digits.is_empty() || digits.len() > 6 {
+            return Err("Unicode escape must have 1 to 6 hex digits.".to_owned());
+        }
+
+        let code = u32::from_str_radix(digits, 16).unwrap();
+        char::from_u32(code).ok_or_else(|| format!("Invalid unicode code point U+{:X}.", code))
     }
 
+    // Decimal numbers with an optional fraction and exponent, or `0x`, `0b` and
+    // `0o` integers. `_` may be used between digits.
     fn number(&mut self) {
-        while self.peek_at(self.current, char::is_numeric) {
+        let radix = if self.source[self.start..].starts_with('0') {
+            match self.peek() {
+                Some('x' | 'X') => Some(16),
+                Some('b' | 'B') => Some(2),
+                Some('o' | 'O') => Some(8),
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        let mut result = match radix {
+            Some(radix) => {
+                self.advance();
+                self.radix_number(radix)
+            }
+            None => self.decimal_number(),
+        };
+
+        // Swallow the rest of something like `12abc` so it is reported once
+        // instead of turning into a number followed by an identifier.
+        if let Some(c) = self.peek().filter(|c| c.is_alphanumeric() || *c == '_') {
+            self.digits(char::is_alphanumeric);
+            if result.is_ok() {
+                result = Err(format!("Invalid character '{}' in number literal.", c));
+            }
+        }
+
+        match result {
+            Ok(value) => self.push_token_with_num(TokenType::Number, value),
+            Err(msg) => self.report(ScanError::InvalidNumber(self.span(), msg)),
+        }
+    }
+
+    fn decimal_number(&mut self) -> Result<f64, String> {
+        self.digits(|c| c.is_ascii_digit());
+        let mut separators = Self::check_separators(&self.source[self.start..self.current]);
+
+        if self.peek_char('.') && self.peek_at(1, |c| c.is_ascii_digit()) {
             self.advance();
+            let fraction = self.digits(|c| c.is_ascii_digit());
+            separators =
+                separators.and(Self::check_separators(&self.source[fraction..self.current]));
         }
 
-        if self.peek_char('.') && self.peek_at(self.current + 1, char::is_numeric) {
+        if self.peek_at(0, |c| c == 'e' || c == 'E') {
             self.advance();
-            while self.peek_at(self.current, char::is_numeric) {
+            if self.peek_at(0, |c| c == '+' || c == '-') {
                 self.advance();
             }
+            let exponent = self.digits(|c| c.is_ascii_digit());
+            if exponent == self.current {
+                return Err("Expected digits in exponent.".to_owned());
+            }
+            separators =
+                separators.and(Self::check_separators(&self.source[exponent..self.current]));
+        }
+        separators?;
+
+        let text = &self.source[self.start..self.current];
+        let value = if text.contains('_') {
+            text.replace('_', "").parse()
+        } else {
+            text.parse()
+        };
+        value.map_err(|_| format!("Invalid number literal '{}'.", text))
+    }
+
+    fn radix_number(&mut self, radix: u32) -> Result<f64, String> {
+        let prefix_end = self.current;
+        let start = self.digits(|c| c.is_ascii_alphanumeric());
+        let prefix = &self.source[self.start..prefix_end];
+        let digits = &self.source[start..self.current];
+        let name = match radix {
+            16 => "hexadecimal",
+            8 => "octal",
+            _ => "binary",
+        };
+
+        if let Some(c) = digits.chars().find(|c| *c != '_' && !c.is_digit(radix)) {
+            return Err(format!("Invalid digit '{}' in {} literal.", c, name));
+        }
+        if digits.chars().all(|c| c == '_') {
+            return Err(format!("Expected digits after '{}'.", prefix));
+        }
+        Self::check_separators(digits)?;
+
+        u64::from_str_radix(&digits.replace('_', ""), radix)
+            .map(|value| value as f64)
+            .map_err(|_| format!("{} literal is too large.", prefix))
+    }
+
+    // Consumes digits accepted by `accept` along with any `_` separators and
+    // returns where they start.
+    fn digits(&mut self, accept: fn(char) -> bool) -> usize {
+        let start = self.current;
+        while self.peek_at(0, |c| accept(c) || c == '_') {
+            self.advance();
         }
+        start
+    }
 
-        let value = self.substring(self.start, self.current);
-        let val = value.parse().unwrap();
-        self.push_token_with_num(TokenType::Number, val)
+    fn check_separators(digits: &str) -> Result<(), String> {
+        if digits.starts_with('_') || digits.ends_with('_') || digits.contains("__") {
+            return Err("Digit separator '_' must be between digits.".to_owned());
+        }
+        Ok(())
     }
 
+    // Identifiers are `_` or XID_Start followed by any XID_Continue, which
+    // covers digits and `_`.
     fn identifier(&mut self) {
-        while self.peek_at(self.current, char::is_alphanumeric) {
+        while self.peek_at(0, UnicodeXID::is_xid_continue) {
             self.advance();
         }
 
-        let value = self.substring(self.start, self.current);
-        self.push_token_with_str(TokenType::from(value.as_str()), value)
+        let t = TokenType::keyword(&self.source[self.start..self.current], &self.options);
+        self.push_token(t)
     }
 
-    fn peek_at<F>(&self, i: usize, f: F) -> bool
+    // Looks `ahead` chars past the current one.
+    fn peek_at<F>(&self, ahead: usize, f: F) -> bool
     where
         F: FnOnce(char) -> bool,
     {
-        if !self.has_more() {
-            return false;
+        self.source[self.current..]
+            .chars()
+            .nth(ahead)
+            .map(f)
+            .unwrap_or(false)
+    }
+}
+
+// A line of a `"""` string, split into its leading whitespace and the rest.
+#[derive(Default)]
+struct StringLine {
+    indent: String,
+    text: String,
+}
+
+impl StringLine {
+    fn is_blank(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    // A blank first line, i.e. a line break right after the opening quotes,
+    // is dropped, and so is a blank last line holding the closing quotes. The
+    // indentation common to the other lines (and the closing quotes) is
+    // removed from each of them, blank lines become empty. The first line
+    // starts after the opening quotes, so it is kept as written.
+    fn join(mut lines: Vec<StringLine>) -> String {
+        let first = lines.remove(0);
+        let first = (lines.is_empty() || !first.is_blank()).then_some(first);
+        let closing = lines.pop_if(|line| line.is_blank());
+
+        let indent = lines
+            .iter()
+            .filter(|line| !line.is_blank())
+            .chain(&closing)
+            .map(|line| line.indent.as_str())
+            .reduce(|common, indent| {
+                let len = common
+                    .bytes()
+                    .zip(indent.bytes())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                &common[..len]
+            })
+            .unwrap_or("");
+
+        let first = first.map(|line| line.indent + &line.text);
+        let rest = lines.iter().map(|line| {
+            if line.is_blank() {
+                String::new()
+            } else {
+                format!("{}{}", &line.indent[indent.len()..], line.text)
+            }
+        });
+        first.into_iter().chain(rest).collect::<Vec<_>>().join("\n")
+    }
+}
+
+// Yields tokens as they are scanned, ending with `Eof`. One token is always
+// held back until the next one is scanned, since in lossless mode that is when
+// its trailing trivia is known.
+impl Iterator for Scanner {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        while self.tokens.len() < 2 && self.scan_next() {}
+
+        if self.tokens.is_empty() {
+            return None;
         }
-        self.char_at(i).map(f).unwrap_or(false)
+        Some(self.tokens.remove(0))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        riolox::token::{TokenLiteral, TokenType},
+        riolox::{
+            error::ScanError,
+            options::LanguageOptions,
+            span::{FileId, Span},
+            token::{Token, TokenLiteral, TokenType, Trivia, TriviaKind},
+        },
         *,
     };
+    use std::{
+        cell::Cell,
+        io::{BufRead, Cursor, Read},
+        rc::Rc,
+    };
 
     #[test]
     fn parenthesis() {
@@ -313,7 +955,10 @@ mod tests {
         assert_eq!(tokens[0].token_type(), &TokenType::LeftBrace);
         assert_eq!(tokens[1].line(), 1);
         assert_eq!(tokens[1].token_type(), &TokenType::String);
-        assert_eq!(tokens[1].literal(), &TokenLiteral::Str("hello".to_owned()));
+        assert_eq!(
+            tokens[1].literal(),
+            Some(&TokenLiteral::Str("hello".to_owned()))
+        );
         assert_eq!(tokens[2].line(), 1);
         assert_eq!(tokens[2].token_type(), &TokenType::RightBrace);
         assert_eq!(tokens[3].line(), 1);
@@ -352,7 +997,10 @@ mod tests {
 
         assert_eq!(tokens.len(), 2);
         assert_eq!(tokens[0].token_type(), &TokenType::String);
-        assert_eq!(tokens[0].literal(), &TokenLiteral::Str("hello".to_owned()));
+        assert_eq!(
+            tokens[0].literal(),
+            Some(&TokenLiteral::Str("hello".to_owned()))
+        );
         assert_eq!(tokens[1].token_type(), &TokenType::Eof);
     }
 
@@ -365,6 +1013,44 @@ mod tests {
 
         assert_eq!(tokens.len(), 1);
         assert_eq!(tokens[0].token_type(), &TokenType::Eof);
+        assert_eq!(
+            scanner.errors(),
+            &[ScanError::UnterminatedString(Span::new(
+                FileId(0),
+                0,
+                6,
+                1,
+                1
+            ))]
+        );
+    }
+
+    #[test]
+    fn unexpected_character() {
+        let source = "1 @ 2".to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(
+            scanner.errors(),
+            &[ScanError::UnexpectedCharacter(
+                Span::new(FileId(0), 2, 3, 1, 3),
+                '@'
+            )]
+        );
+        assert_eq!(scanner.errors()[0].to_string(), "Unexpected character '@'.");
+    }
+
+    #[test]
+    fn no_errors() {
+        let source = "(1 + \"a\") // fine".to_owned();
+        let mut scanner = Scanner::new(source);
+
+        scanner.scan_tokens();
+
+        assert!(!scanner.had_error());
     }
 
     #[test]
@@ -376,7 +1062,7 @@ mod tests {
 
         assert_eq!(tokens.len(), 2);
         assert_eq!(tokens[0].token_type(), &TokenType::Number);
-        assert_eq!(tokens[0].literal(), &TokenLiteral::Num(123.0));
+        assert_eq!(tokens[0].literal(), Some(&TokenLiteral::Num(123.0)));
         assert_eq!(tokens[1].token_type(), &TokenType::Eof);
     }
 
@@ -389,7 +1075,7 @@ mod tests {
 
         assert_eq!(tokens.len(), 2);
         assert_eq!(tokens[0].token_type(), &TokenType::Number);
-        assert_eq!(tokens[0].literal(), &TokenLiteral::Num(123.5));
+        assert_eq!(tokens[0].literal(), Some(&TokenLiteral::Num(123.5)));
         assert_eq!(tokens[1].token_type(), &TokenType::Eof);
     }
 
@@ -403,7 +1089,8 @@ mod tests {
         println!("{:?}", tokens);
         assert_eq!(tokens.len(), 2);
         assert_eq!(tokens[0].token_type(), &TokenType::Identifier);
-        assert_eq!(tokens[0].literal(), &TokenLiteral::Str("witam".to_owned()));
+        assert_eq!(tokens[0].lexeme(), "witam");
+        assert_eq!(tokens[0].literal(), None);
         assert_eq!(tokens[1].token_type(), &TokenType::Eof);
     }
 
@@ -416,7 +1103,8 @@ mod tests {
 
         assert_eq!(tokens.len(), 2);
         assert_eq!(tokens[0].token_type(), &TokenType::Identifier);
-        assert_eq!(tokens[0].literal(), &TokenLiteral::Str("witam2".to_owned()));
+        assert_eq!(tokens[0].lexeme(), "witam2");
+        assert_eq!(tokens[0].literal(), None);
         assert_eq!(tokens[1].token_type(), &TokenType::Eof);
     }
 
@@ -447,4 +1135,1001 @@ mod tests {
         assert_eq!(tokens[16].token_type(), &TokenType::Identifier);
         assert_eq!(tokens[17].token_type(), &TokenType::Eof);
     }
+
+    #[test]
+    fn token_spans() {
+        let source = "(1 +\n  foo)".to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(tokens[0].span(), &Span::new(FileId(0), 0, 1, 1, 1));
+        assert_eq!(tokens[1].span(), &Span::new(FileId(0), 1, 2, 1, 2));
+        assert_eq!(tokens[2].span(), &Span::new(FileId(0), 3, 4, 1, 4));
+        assert_eq!(tokens[3].span(), &Span::new(FileId(0), 7, 10, 2, 3));
+        assert_eq!(tokens[4].span(), &Span::new(FileId(0), 10, 11, 2, 6));
+        assert_eq!(tokens[5].span(), &Span::new(FileId(0), 11, 11, 2, 7));
+    }
+
+    #[test]
+    fn multiline_string_span_starts_at_opening_quote() {
+        let source = "  \"a\nb\" x".to_owned();
+        let mut scanner = Scanner::with_file(source, FileId(3));
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[0].token_type(), &TokenType::String);
+        assert_eq!(tokens[0].span(), &Span::new(FileId(3), 2, 7, 1, 3));
+        assert_eq!(tokens[1].token_type(), &TokenType::Identifier);
+        assert_eq!(tokens[1].span(), &Span::new(FileId(3), 8, 9, 2, 4));
+    }
+
+    #[test]
+    fn multibyte_string() {
+        let source = "\"zażółć gęślą 🦀\" ;".to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[0].token_type(), &TokenType::String);
+        assert_eq!(
+            tokens[0].literal(),
+            Some(&TokenLiteral::Str("zażółć gęślą 🦀".to_owned()))
+        );
+        assert_eq!(tokens[1].token_type(), &TokenType::Semicolon);
+        assert_eq!(tokens[1].span(), &Span::new(FileId(0), 27, 28, 1, 18));
+        assert_eq!(tokens[2].token_type(), &TokenType::Eof);
+    }
+
+    #[test]
+    fn multibyte_identifier() {
+        let source = "źdźbło + 1".to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[0].token_type(), &TokenType::Identifier);
+        assert_eq!(tokens[0].lexeme(), "źdźbło");
+        assert_eq!(tokens[0].literal(), None);
+        assert_eq!(tokens[0].span(), &Span::new(FileId(0), 0, 9, 1, 1));
+        assert_eq!(tokens[1].token_type(), &TokenType::Plus);
+        assert_eq!(tokens[1].column(), 8);
+        assert_eq!(tokens[2].literal(), Some(&TokenLiteral::Num(1.0)));
+    }
+
+    #[test]
+    fn multibyte_in_comment() {
+        let source = "// ünïcödé ✓\n42".to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].literal(), Some(&TokenLiteral::Num(42.0)));
+        assert_eq!(tokens[0].line(), 2);
+        assert_eq!(tokens[0].column(), 1);
+    }
+
+    #[test]
+    fn large_input() {
+        let line = "(\"ść\" == 12.5) != !true; // comment\n";
+        let source = line.repeat(50_000);
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 50_000 * 9 + 1);
+        let last = &tokens[tokens.len() - 2];
+        assert_eq!(last.token_type(), &TokenType::Semicolon);
+        assert_eq!(last.line(), 50_000);
+        assert_eq!(last.column(), 24);
+        assert_eq!(tokens[tokens.len() - 1].line(), 50_001);
+    }
+
+    #[test]
+    fn string_escapes() {
+        let source = r#""a\nb\tc\rd\"e\\f\0g""#.to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token_type(), &TokenType::String);
+        assert_eq!(
+            tokens[0].literal(),
+            Some(&TokenLiteral::Str("a\nb\tc\rd\"e\\f\0g".to_owned()))
+        );
+        assert_eq!(tokens[0].span().end, 21);
+    }
+
+    #[test]
+    fn string_unicode_escapes() {
+        let source = r#""\u{41}\u{df}\u{1F980}""#.to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(
+            tokens[0].literal(),
+            Some(&TokenLiteral::Str("Aß🦀".to_owned()))
+        );
+    }
+
+    #[test]
+    fn string_unknown_escape_is_dropped() {
+        let source = r#""a\qb" 1"#.to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(
+            tokens[0].literal(),
+            Some(&TokenLiteral::Str("ab".to_owned()))
+        );
+        assert_eq!(tokens[1].token_type(), &TokenType::Number);
+        assert_eq!(
+            scanner.errors(),
+            &[ScanError::UnknownEscape(
+                Span::new(FileId(0), 2, 4, 1, 3),
+                'q'
+            )]
+        );
+    }
+
+    #[test]
+    fn string_malformed_unicode_escapes() {
+        for source in [
+            r#""\u41""#,
+            r#""\u{}""#,
+            r#""\u{1234567}""#,
+            r#""\u{D800}""#,
+            r#""\u{110000}""#,
+            r#""\u{41""#,
+        ] {
+            let mut scanner = Scanner::new(source.to_owned());
+
+            let tokens = scanner.scan_tokens();
+
+            assert_eq!(tokens.len(), 2, "{}", source);
+            assert_eq!(tokens[0].token_type(), &TokenType::String, "{}", source);
+            assert!(
+                matches!(scanner.errors(), [ScanError::InvalidUnicodeEscape(span, _)] if span.start == 1),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn escaped_quote_does_not_terminate_string() {
+        let source = r#""abc\""#.to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].token_type(), &TokenType::Eof);
+    }
+
+    #[test]
+    fn string_interpolation() {
+        let source = r#""Hello ${name}, you have ${count + 1} items""#.to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        let types: Vec<&TokenType> = tokens.iter().map(|t| t.token_type()).collect();
+        assert_eq!(
+            types,
+            vec![
+                &TokenType::Interpolation,
+                &TokenType::Identifier,
+                &TokenType::Interpolation,
+                &TokenType::Identifier,
+                &TokenType::Plus,
+                &TokenType::Number,
+                &TokenType::String,
+                &TokenType::Eof,
+            ]
+        );
+        assert_eq!(
+            tokens[0].literal(),
+            Some(&TokenLiteral::Str("Hello ".to_owned()))
+        );
+        assert_eq!(
+            tokens[2].literal(),
+            Some(&TokenLiteral::Str(", you have ".to_owned()))
+        );
+        assert_eq!(tokens[2].span(), &Span::new(FileId(0), 13, 27, 1, 14));
+        assert_eq!(
+            tokens[6].literal(),
+            Some(&TokenLiteral::Str(" items".to_owned()))
+        );
+    }
+
+    #[test]
+    fn nested_string_interpolation() {
+        let source = r#""a ${"b ${c}" + {}} d""#.to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        let types: Vec<&TokenType> = tokens.iter().map(|t| t.token_type()).collect();
+        assert_eq!(
+            types,
+            vec![
+                &TokenType::Interpolation,
+                &TokenType::Interpolation,
+                &TokenType::Identifier,
+                &TokenType::String,
+                &TokenType::Plus,
+                &TokenType::LeftBrace,
+                &TokenType::RightBrace,
+                &TokenType::String,
+                &TokenType::Eof,
+            ]
+        );
+        assert_eq!(
+            tokens[7].literal(),
+            Some(&TokenLiteral::Str(" d".to_owned()))
+        );
+    }
+
+    #[test]
+    fn escaped_interpolation() {
+        let source = r#""\${a}""#.to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(
+            tokens[0].literal(),
+            Some(&TokenLiteral::Str("${a}".to_owned()))
+        );
+    }
+
+    #[test]
+    fn unterminated_interpolation() {
+        let source = r#""a ${b"#.to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[0].token_type(), &TokenType::Interpolation);
+        assert_eq!(tokens[1].token_type(), &TokenType::Identifier);
+        assert_eq!(tokens[2].token_type(), &TokenType::Eof);
+        assert_eq!(
+            scanner.errors(),
+            &[ScanError::UnterminatedInterpolation(Span::new(
+                FileId(0),
+                3,
+                5,
+                1,
+                4
+            ))]
+        );
+    }
+
+    #[test]
+    fn block_comment() {
+        let source = "1 /* two\nlines */ 2".to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[0].literal(), Some(&TokenLiteral::Num(1.0)));
+        assert_eq!(tokens[1].literal(), Some(&TokenLiteral::Num(2.0)));
+        assert_eq!(tokens[1].line(), 2);
+        assert_eq!(tokens[1].column(), 10);
+    }
+
+    #[test]
+    fn nested_block_comment() {
+        let source = "/* a /* b */ c */ + /**/ -".to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[0].token_type(), &TokenType::Plus);
+        assert_eq!(tokens[1].token_type(), &TokenType::Minus);
+    }
+
+    #[test]
+    fn unterminated_block_comment() {
+        let source = "+ /* a /* b */".to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token_type(), &TokenType::Plus);
+        assert_eq!(tokens[1].token_type(), &TokenType::Eof);
+        assert_eq!(
+            scanner.errors(),
+            &[ScanError::UnterminatedBlockComment(Span::new(
+                FileId(0),
+                2,
+                14,
+                1,
+                3
+            ))]
+        );
+    }
+
+    #[test]
+    fn doc_comment_attached_to_next_token() {
+        let source =
+            "/// Adds things.\n///\n///   Indented.\nvar x; // plain\n//// not doc\ny".to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 5);
+        assert_eq!(tokens[0].token_type(), &TokenType::Var);
+        assert_eq!(tokens[0].doc(), Some("Adds things.\n\n  Indented."));
+        assert_eq!(tokens[1].doc(), None);
+        assert_eq!(tokens[3].token_type(), &TokenType::Identifier);
+        assert_eq!(tokens[3].doc(), None);
+    }
+
+    fn full_text(tokens: &[Token]) -> String {
+        tokens.iter().map(|t| t.full_text()).collect()
+    }
+
+    #[test]
+    fn lossless_trivia() {
+        let source = "  a /* c */ + // tail\n\tb".to_owned();
+        let mut scanner = Scanner::new(source).lossless();
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 4);
+        let kinds = |trivia: &[Trivia]| trivia.iter().map(|t| t.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds(tokens[0].leading_trivia()),
+            vec![TriviaKind::Whitespace]
+        );
+        assert_eq!(
+            kinds(tokens[0].trailing_trivia()),
+            vec![
+                TriviaKind::Whitespace,
+                TriviaKind::BlockComment,
+                TriviaKind::Whitespace
+            ]
+        );
+        assert_eq!(
+            kinds(tokens[1].trailing_trivia()),
+            vec![TriviaKind::Whitespace, TriviaKind::LineComment]
+        );
+        assert_eq!(
+            kinds(tokens[2].leading_trivia()),
+            vec![TriviaKind::Newline, TriviaKind::Whitespace]
+        );
+        assert_eq!(tokens[2].leading_trivia()[1].text.as_str(), "\t");
+        assert_eq!(
+            tokens[2].leading_trivia()[1].span,
+            Span::new(FileId(0), 22, 23, 2, 1)
+        );
+        assert!(tokens[3].leading_trivia().is_empty());
+    }
+
+    #[test]
+    fn lossless_round_trip() {
+        let sources = [
+            "",
+            "   \n\n",
+            "/// doc\nvar x = \"a\\n${ y + {} }b\"; // end\n",
+            "1 /* a /* b */ */ 2.5\r\n  ! = == ",
+            "@ # 12 \"unterminated",
+            "\"${1\n",
+            "źdźbło /* ✓ */ \"ść\"",
+        ];
+
+        for source in sources {
+            let mut scanner = Scanner::new(source.to_owned()).lossless();
+
+            let tokens = scanner.scan_tokens();
+
+            assert_eq!(full_text(tokens), source);
+        }
+    }
+
+    #[test]
+    fn no_trivia_by_default() {
+        let source = " a // b\n c".to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert!(tokens.iter().all(|t| t.leading_trivia().is_empty()));
+        assert!(tokens.iter().all(|t| t.trailing_trivia().is_empty()));
+    }
+
+    #[test]
+    fn numeric_literal_radixes() {
+        for (source, value) in [
+            ("0xFF", 255.0),
+            ("0Xff", 255.0),
+            ("0b1010", 10.0),
+            ("0o17", 15.0),
+            ("0", 0.0),
+            ("007", 7.0),
+        ] {
+            let tokens = scan(source);
+            assert_eq!(tokens.len(), 2, "{}: {:?}", source, tokens);
+            assert_eq!(tokens[0].literal(), Some(&TokenLiteral::Num(value)));
+        }
+    }
+
+    #[test]
+    fn numeric_literal_separators_and_exponents() {
+        for (source, value) in [
+            ("1_000_000", 1_000_000.0),
+            ("0xFF_FF", 65535.0),
+            ("6.02e23", 6.02e23),
+            ("1E3", 1000.0),
+            ("2.5e-3", 0.0025),
+            ("1e+2", 100.0),
+            ("3_000.000_1", 3000.0001),
+        ] {
+            let tokens = scan(source);
+            assert_eq!(tokens.len(), 2, "{}: {:?}", source, tokens);
+            assert_eq!(tokens[0].literal(), Some(&TokenLiteral::Num(value)));
+        }
+    }
+
+    #[test]
+    fn malformed_numeric_literals() {
+        for source in [
+            "0x",
+            "0xG1",
+            "0b102",
+            "0o8",
+            "0b_",
+            "1__0",
+            "1_",
+            "1e",
+            "1e+",
+            "12abc",
+            "0x1_",
+            "1_e5",
+            "0xFFFFFFFFFFFFFFFFFF",
+        ] {
+            let mut scanner = Scanner::new(source.to_owned());
+            let tokens = scanner.scan_tokens();
+
+            assert_eq!(tokens.len(), 1, "{}: {:?}", source, tokens);
+            assert!(
+                matches!(scanner.errors(), [ScanError::InvalidNumber(span, _)] if span.start == 0),
+                "{}: {:?}",
+                source,
+                scanner.errors()
+            );
+        }
+    }
+
+    #[test]
+    fn number_followed_by_dot() {
+        let source = "1.foo".to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[0].literal(), Some(&TokenLiteral::Num(1.0)));
+        assert_eq!(tokens[1].token_type(), &TokenType::Dot);
+        assert_eq!(tokens[2].token_type(), &TokenType::Identifier);
+    }
+
+    // Serves one line per `fill_buf` and counts how many were handed out.
+    struct Lines {
+        lines: Vec<String>,
+        consumed: usize,
+        served: Rc<Cell<usize>>,
+    }
+
+    impl Read for Lines {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.fill_buf()?.len().min(buf.len());
+            buf[..n].copy_from_slice(&self.fill_buf()?[..n]);
+            self.consume(n);
+            Ok(n)
+        }
+    }
+
+    impl BufRead for Lines {
+        fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+            let served = self.served.get();
+            if served > 0 && self.consumed < self.lines[served - 1].len() {
+                return Ok(&self.lines[served - 1].as_bytes()[self.consumed..]);
+            }
+            if served == self.lines.len() {
+                return Ok(&[]);
+            }
+            self.served.set(served + 1);
+            self.consumed = 0;
+            Ok(self.lines[served].as_bytes())
+        }
+
+        fn consume(&mut self, amt: usize) {
+            self.consumed += amt;
+        }
+    }
+
+    fn summary(tokens: &[Token]) -> Vec<(TokenType, &str, Option<&TokenLiteral>, Span)> {
+        tokens
+            .iter()
+            .map(|t| (t.token_type().clone(), t.lexeme(), t.literal(), *t.span()))
+            .collect()
+    }
+
+    // Scans a source that has no errors in it.
+    fn scan(source: &str) -> Vec<Token> {
+        let mut scanner = Scanner::new(source.to_owned());
+        let tokens = scanner.scan_tokens().to_vec();
+
+        assert!(!scanner.had_error(), "{}: {:?}", source, scanner.errors());
+        tokens
+    }
+
+    #[test]
+    fn reader_matches_scan_tokens() {
+        let source =
+            "var a = \"multi\nline ${ b +\n{} }\";\n/* block\n /* comment */ */ 0x1F\n/// doc\nc";
+        let mut scanner = Scanner::new(source.to_owned());
+        let expected = scanner.scan_tokens().to_vec();
+
+        let streamed: Vec<Token> = Scanner::from_reader(Cursor::new(source.to_owned())).collect();
+
+        assert_eq!(summary(&streamed), summary(&expected));
+        let c = &streamed[streamed.len() - 2];
+        assert_eq!(c.doc(), Some("doc"));
+        assert_eq!(c.span().line, 7);
+    }
+
+    #[test]
+    fn reader_lexemes_spanning_lines() {
+        let body = "line\n".repeat(10_000);
+        let source = format!("a \"{body}\" /* {body} */ b");
+
+        let tokens: Vec<Token> = Scanner::from_reader(Cursor::new(source.clone()))
+            .lossless()
+            .collect();
+
+        assert_eq!(tokens[1].lexeme(), format!("\"{body}\""));
+        assert_eq!(tokens[2].lexeme(), "b");
+        let text: String = tokens.iter().map(|t| t.full_text()).collect();
+        assert_eq!(text, source);
+    }
+
+    #[test]
+    fn reader_is_lazy() {
+        let served = Rc::new(Cell::new(0));
+        let lines = Lines {
+            lines: vec![
+                "1;\n".into(),
+                "\"a\n".into(),
+                "b\";\n".into(),
+                "2;\n".into(),
+            ],
+            consumed: 0,
+            served: served.clone(),
+        };
+        let mut scanner = Scanner::from_reader(lines);
+
+        assert_eq!(scanner.next().unwrap().token_type(), &TokenType::Number);
+        assert_eq!(served.get(), 1);
+
+        let string = scanner.next().unwrap();
+        assert_eq!(string.token_type(), &TokenType::Semicolon);
+        assert_eq!(served.get(), 3);
+
+        let string = scanner.next().unwrap();
+        assert_eq!(
+            string.literal(),
+            Some(&TokenLiteral::Str("a\nb".to_owned()))
+        );
+        assert_eq!(string.span(), &Span::new(FileId(0), 3, 8, 2, 1));
+        assert_eq!(served.get(), 3);
+
+        assert_eq!(scanner.count(), 4);
+        assert_eq!(served.get(), 4);
+    }
+
+    #[test]
+    fn reader_lossless_round_trip() {
+        let source = "  a // x\n/* b\n c */ \"d\n\" \n";
+        let scanner = Scanner::from_reader(Cursor::new(source.to_owned())).lossless();
+
+        let text: String = scanner.map(|t| t.full_text()).collect();
+
+        assert_eq!(text, source);
+    }
+
+    #[test]
+    fn reader_reports_errors() {
+        let mut scanner = Scanner::from_reader(Cursor::new("1\n\"open\n".to_owned()));
+
+        assert_eq!(scanner.by_ref().count(), 2);
+        assert_eq!(
+            scanner.errors(),
+            &[ScanError::UnterminatedString(Span::new(
+                FileId(0),
+                2,
+                8,
+                2,
+                1
+            ))]
+        );
+    }
+
+    #[test]
+    fn needs_more_input() {
+        for source in ["\"abc", "/* a", "\"${a", "{ 1", "(1 +\n2", "a ${"] {
+            let mut scanner = Scanner::new(source.to_owned());
+            scanner.scan_tokens();
+            assert!(scanner.needs_more_input(), "{}", source);
+        }
+
+        for source in [
+            "\"abc\"", "/* a */", "\"${a}\"", "{ 1 }", "(1 +\n2)", "1 @", "}",
+        ] {
+            let mut scanner = Scanner::new(source.to_owned());
+            scanner.scan_tokens();
+            assert!(!scanner.needs_more_input(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn snake_case_identifiers() {
+        let tokens = scan("my_var _private __dunder__ _ a_1_b");
+
+        assert!(tokens[..5]
+            .iter()
+            .all(|t| t.token_type() == &TokenType::Identifier));
+        assert_eq!(
+            tokens[..5].iter().map(|t| t.lexeme()).collect::<Vec<_>>(),
+            vec!["my_var", "_private", "__dunder__", "_", "a_1_b"]
+        );
+    }
+
+    #[test]
+    fn non_latin_identifiers() {
+        let tokens = scan("привет 日本語 αβγ_δ żółw café");
+
+        assert!(tokens[..5]
+            .iter()
+            .all(|t| t.token_type() == &TokenType::Identifier));
+        assert_eq!(
+            tokens[..5].iter().map(|t| t.lexeme()).collect::<Vec<_>>(),
+            vec!["привет", "日本語", "αβγ_δ", "żółw", "café"]
+        );
+    }
+
+    #[test]
+    fn keywords_are_not_identifiers_with_underscores() {
+        let source = "var _var var_".to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens[0].token_type(), &TokenType::Var);
+        assert_eq!(tokens[1].token_type(), &TokenType::Identifier);
+        assert_eq!(tokens[2].token_type(), &TokenType::Identifier);
+    }
+
+    #[test]
+    fn invalid_identifier_starts() {
+        for (source, c) in [
+            ("²", '²'),
+            ("\u{301}a", '\u{301}'),
+            ("🦀", '🦀'),
+            ("x²", '²'),
+        ] {
+            let mut scanner = Scanner::new(source.to_owned());
+
+            scanner.scan_tokens();
+
+            assert!(
+                matches!(scanner.errors(), [ScanError::UnexpectedCharacter(_, found)] if *found == c),
+                "{}: {:?}",
+                source,
+                scanner.errors()
+            );
+        }
+    }
+
+    #[test]
+    fn digit_starts_number_not_identifier() {
+        let source = "1abc".to_owned();
+        let mut scanner = Scanner::new(source);
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 1);
+        assert!(matches!(scanner.errors(), [ScanError::InvalidNumber(_, _)]));
+    }
+
+    #[test]
+    fn interpolation_disabled_in_strict_dialect() {
+        let source = r#""a ${b}""#.to_owned();
+        let mut scanner = Scanner::new(source).with_options(LanguageOptions::strict());
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token_type(), &TokenType::String);
+        assert_eq!(
+            tokens[0].literal(),
+            Some(&TokenLiteral::Str("a ${b}".to_owned()))
+        );
+    }
+
+    #[test]
+    fn extra_keywords_only_when_enabled() {
+        let source = "break continue";
+
+        let types = |options| -> Vec<TokenType> {
+            Scanner::new(source.to_owned())
+                .with_options(options)
+                .map(|t| t.t)
+                .collect()
+        };
+
+        assert_eq!(
+            types(LanguageOptions::extended()),
+            vec![TokenType::Break, TokenType::Continue, TokenType::Eof]
+        );
+        assert_eq!(
+            types(LanguageOptions::strict()),
+            vec![TokenType::Identifier, TokenType::Identifier, TokenType::Eof]
+        );
+    }
+
+    #[test]
+    fn book_keywords_in_every_dialect() {
+        for options in [LanguageOptions::strict(), LanguageOptions::extended()] {
+            let source = "class fun var while nothing".to_owned();
+            let mut scanner = Scanner::new(source).with_options(options);
+
+            let tokens = scanner.scan_tokens();
+
+            let types: Vec<&TokenType> = tokens.iter().map(|t| t.token_type()).collect();
+            assert_eq!(
+                types,
+                vec![
+                    &TokenType::Class,
+                    &TokenType::Fun,
+                    &TokenType::Var,
+                    &TokenType::While,
+                    &TokenType::Identifier,
+                    &TokenType::Eof
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn punctuation_lexemes() {
+        let source = "( ) { } , . - + ; / * ? : ! != = == > >= < <=";
+
+        let tokens = scan(source);
+
+        let expected: Vec<&str> = source.split(' ').chain([""]).collect();
+        assert_eq!(
+            tokens.iter().map(|t| t.lexeme()).collect::<Vec<_>>(),
+            expected
+        );
+        assert!(tokens.iter().all(|t| t.literal().is_none()));
+        assert_eq!(tokens[4].token_type(), &TokenType::Comma);
+        assert_eq!(tokens[14].token_type(), &TokenType::BangEqual);
+        assert_eq!(tokens[21].token_type(), &TokenType::Eof);
+    }
+
+    #[test]
+    fn keyword_and_identifier_lexemes() {
+        let source =
+            "and class else false for fun if nil or print return super this true var while ab_1";
+
+        let tokens = scan(source);
+
+        for (token, text) in tokens.iter().zip(source.split(' ')) {
+            assert_eq!(token.lexeme(), text);
+            assert_eq!(token.literal(), None, "{:?}", token.token_type());
+        }
+        assert_eq!(tokens[16].token_type(), &TokenType::Identifier);
+    }
+
+    #[test]
+    fn literal_lexemes() {
+        let tokens = scan(r#""a\tb" 1_000 0x1F 2.5e1"#);
+
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|t| (t.token_type().clone(), t.lexeme(), t.literal().cloned()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    TokenType::String,
+                    r#""a\tb""#,
+                    Some(TokenLiteral::from("a\tb"))
+                ),
+                (TokenType::Number, "1_000", Some(TokenLiteral::Num(1000.0))),
+                (TokenType::Number, "0x1F", Some(TokenLiteral::Num(31.0))),
+                (TokenType::Number, "2.5e1", Some(TokenLiteral::Num(25.0))),
+                (TokenType::Eof, "", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn interpolation_lexemes() {
+        let tokens = scan(r#""a ${b} c""#);
+
+        let lexemes: Vec<&str> = tokens.iter().map(|t| t.lexeme()).collect();
+        assert_eq!(lexemes, vec![r#""a ${"#, "b", r#"} c""#, ""]);
+        assert_eq!(tokens[0].literal(), Some(&TokenLiteral::from("a ")));
+        assert_eq!(tokens[2].literal(), Some(&TokenLiteral::from(" c")));
+    }
+
+    #[test]
+    fn raw_strings() {
+        for (source, value) in [
+            (r#"r"a\nb""#, r"a\nb"),
+            (r#"r"${x}""#, "${x}"),
+            (r##"r#"say "hi""#"##, r#"say "hi""#),
+            (r###"r##"a "# b"##"###, r##"a "# b"##),
+            ("r\"a\nb\"", "a\nb"),
+        ] {
+            let tokens = scan(source);
+            assert_eq!(tokens.len(), 2, "{}: {:?}", source, tokens);
+            assert_eq!(tokens[0].lexeme(), source);
+            assert_eq!(tokens[0].literal(), Some(&TokenLiteral::from(value)));
+        }
+    }
+
+    #[test]
+    fn raw_string_span() {
+        let mut scanner = Scanner::new("x r#\"a\nb\"# y".to_owned());
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens[1].span(), &Span::new(FileId(0), 2, 10, 1, 3));
+        assert_eq!(tokens[2].span(), &Span::new(FileId(0), 11, 12, 2, 5));
+    }
+
+    #[test]
+    fn r_without_quote_is_an_identifier() {
+        let tokens = scan("r r_ rx");
+        assert!(tokens[..3]
+            .iter()
+            .all(|t| t.token_type() == &TokenType::Identifier));
+        assert_eq!(
+            tokens[..3].iter().map(|t| t.lexeme()).collect::<Vec<_>>(),
+            vec!["r", "r_", "rx"]
+        );
+
+        let mut scanner = Scanner::new("r#a".to_owned());
+        scanner.scan_tokens();
+        assert_eq!(
+            scanner.errors(),
+            &[ScanError::UnexpectedCharacter(
+                Span::new(FileId(0), 1, 2, 1, 2),
+                '#'
+            )]
+        );
+    }
+
+    #[test]
+    fn unterminated_raw_string() {
+        let mut scanner = Scanner::new("r#\"abc\" ".to_owned());
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(
+            scanner.errors(),
+            &[ScanError::UnterminatedString(Span::new(
+                FileId(0),
+                0,
+                8,
+                1,
+                1
+            ))]
+        );
+        assert!(scanner.needs_more_input());
+    }
+
+    #[test]
+    fn multiline_strings() {
+        for (source, value) in [
+            (
+                "\"\"\"\n    SELECT *\n      FROM t\n\n    WHERE a = \\\"${b}\\\"\n    \"\"\"",
+                "SELECT *\n  FROM t\n\nWHERE a = \"${b}\"",
+            ),
+            // The closing quotes count towards the indentation.
+            ("\"\"\"\n    a\n      b\n  \"\"\"", "  a\n    b"),
+            // Text after the opening quotes is kept as written.
+            ("\"\"\"  a\n    b\n    c\"\"\"", "  a\nb\nc"),
+            ("\"\"\"a \"quoted\" \"\"b\"\"\"", "a \"quoted\" \"\"b"),
+            ("\"\"\"\r\n  a\r\n  b\r\n  \"\"\"", "a\nb"),
+            ("\"\"\"\n\t\\tx\n\t\"\"\"", "\tx"),
+            ("\"\"\"\"\"\"", ""),
+        ] {
+            let tokens = scan(source);
+            assert_eq!(tokens.len(), 2, "{}: {:?}", source, tokens);
+            assert_eq!(tokens[0].token_type(), &TokenType::String);
+            assert_eq!(tokens[0].literal(), Some(&TokenLiteral::from(value)));
+        }
+    }
+
+    #[test]
+    fn multiline_string_errors_have_precise_spans() {
+        let source = "\"\"\"\n  ok\n  bad \\q\n  \"\"\"";
+        let mut scanner = Scanner::new(source.to_owned());
+
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(
+            tokens[0].span(),
+            &Span::new(FileId(0), 0, source.len(), 1, 1)
+        );
+        assert_eq!(tokens[0].literal(), Some(&TokenLiteral::from("ok\nbad ")));
+        assert_eq!(
+            scanner.errors(),
+            &[ScanError::UnknownEscape(
+                Span::new(FileId(0), 15, 17, 3, 7),
+                'q'
+            )]
+        );
+    }
+
+    #[test]
+    fn unterminated_multiline_string() {
+        let mut scanner = Scanner::new("\"\"\"\n  a \"\"".to_owned());
+
+        scanner.scan_tokens();
+
+        assert_eq!(
+            scanner.errors(),
+            &[ScanError::UnterminatedString(Span::new(
+                FileId(0),
+                0,
+                10,
+                1,
+                1
+            ))]
+        );
+        assert!(scanner.needs_more_input());
+    }
+
+    #[test]
+    fn raw_and_multiline_strings_disabled_in_strict_dialect() {
+        let mut scanner =
+            Scanner::new("r\"a\" \"\"\"b\"".to_owned()).with_options(LanguageOptions::strict());
+
+        let types: Vec<TokenType> = scanner.scan_tokens().iter().map(|t| t.t.clone()).collect();
+
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Identifier,
+                TokenType::String,
+                TokenType::String,
+                TokenType::String,
+                TokenType::Eof
+            ]
+        );
+    }
 }
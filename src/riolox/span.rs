@@ -0,0 +1,32 @@
+use std::fmt::Display;
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash)]
+pub(crate) struct FileId(pub usize);
+
+// Byte range of a token in its file, plus the line and column (both 1-based) it starts at.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub(crate) struct Span {
+    pub file: FileId,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(file: FileId, start: usize, end: usize, line: usize, column: usize) -> Self {
+        Span {
+            file,
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
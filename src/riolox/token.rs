@@ -1,4 +1,6 @@
-use std::fmt::Display;
+use std::{fmt::Display, rc::Rc};
+
+use super::{options::LanguageOptions, span::Span};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub(crate) enum TokenType {
@@ -30,6 +32,7 @@ pub(crate) enum TokenType {
     // Literals
     Identifier,
     String,
+    Interpolation,
     Number,
 
     // Keywords
@@ -49,6 +52,8 @@ pub(crate) enum TokenType {
     True,
     Var,
     While,
+    Break,
+    Continue,
 
     Eof,
 }
@@ -77,11 +82,33 @@ impl From<&str> for TokenType {
     }
 }
 
+// Keywords rio-lox adds on top of book Lox. Without
+// `LanguageOptions::extra_keywords` they are scanned as plain identifiers.
+const EXTRA_KEYWORDS: &[(&str, TokenType)] = &[
+    ("break", TokenType::Break),
+    ("continue", TokenType::Continue),
+];
+
+impl TokenType {
+    // Like `from`, but also reserves `EXTRA_KEYWORDS` when `options` enable them.
+    pub fn keyword(value: &str, options: &LanguageOptions) -> Self {
+        match TokenType::from(value) {
+            TokenType::Identifier if options.extra_keywords => EXTRA_KEYWORDS
+                .iter()
+                .find(|(keyword, _)| *keyword == value)
+                .map(|(_, t)| t.clone())
+                .unwrap_or(TokenType::Identifier),
+            t => t,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) enum TokenLiteral {
     Str(String),
     Num(f64),
     Bool(bool),
+    Nil,
 }
 
 impl From<&str> for TokenLiteral {
@@ -96,34 +123,129 @@ impl Display for TokenLiteral {
             TokenLiteral::Str(s) => f.write_str(s),
             TokenLiteral::Num(n) => f.write_str(n.to_string().as_str()),
             TokenLiteral::Bool(b) => f.write_str(b.to_string().as_str()),
+            TokenLiteral::Nil => f.write_str("nil"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum TriviaKind {
+    Whitespace,
+    Newline,
+    LineComment,
+    DocComment,
+    BlockComment,
+    // Input the scanner reported an error for and could not turn into a token.
+    Skipped,
+}
+
+impl From<&str> for TriviaKind {
+    fn from(text: &str) -> Self {
+        if text.starts_with("///") && !text.starts_with("////") {
+            TriviaKind::DocComment
+        } else if text.starts_with("//") {
+            TriviaKind::LineComment
+        } else if text.starts_with("/*") {
+            TriviaKind::BlockComment
+        } else if text == "\n" {
+            TriviaKind::Newline
+        } else if text.chars().all(|c| c == ' ' || c == '\r' || c == '\t') {
+            TriviaKind::Whitespace
+        } else {
+            TriviaKind::Skipped
         }
     }
 }
 
+// A piece of source text: a shared handle to the text it was scanned from
+// plus a byte range into it, so tokens and trivia don't copy their text.
+#[derive(Clone)]
+pub(crate) struct Lexeme {
+    source: Rc<str>,
+    start: usize,
+    end: usize,
+}
+
+impl Lexeme {
+    pub fn new(source: Rc<str>, start: usize, end: usize) -> Self {
+        debug_assert!(source.is_char_boundary(start) && source.is_char_boundary(end));
+        Lexeme { source, start, end }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source[self.start..self.end]
+    }
+
+    // Grows the lexeme to end at `end` in the same source.
+    pub fn extend_to(&mut self, end: usize) {
+        self.end = end;
+    }
+
+    pub fn shares_source(&self, source: &Rc<str>) -> bool {
+        Rc::ptr_eq(&self.source, source)
+    }
+}
+
+impl From<&str> for Lexeme {
+    fn from(value: &str) -> Self {
+        Lexeme::new(Rc::from(value), 0, value.len())
+    }
+}
+
+impl std::fmt::Debug for Lexeme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Trivia {
+    pub kind: TriviaKind,
+    pub text: Lexeme,
+    pub span: Span,
+}
+
+// Doc comments and trivia are rare outside of tooling, so they live behind an
+// `Rc` that most tokens never allocate.
+#[derive(Debug, Clone, Default)]
+struct TokenExtras {
+    doc: Option<String>,
+    leading_trivia: Vec<Trivia>,
+    trailing_trivia: Vec<Trivia>,
+}
+
+// Only strings, interpolation segments and numbers carry a literal value.
+// Everything a token owns is shared, so the parser can clone tokens into the
+// syntax tree without copying any text.
 #[derive(Debug, Clone)]
 pub(crate) struct Token {
     pub t: TokenType,
-    literal: TokenLiteral,
-    line: usize,
-    lexeme: String,
+    literal: Option<Rc<TokenLiteral>>,
+    span: Span,
+    lexeme: Lexeme,
+    extras: Option<Rc<TokenExtras>>,
 }
 
 impl Token {
-    pub fn new(t: TokenType, literal: String, lexeme: &str, line: usize) -> Self {
+    pub fn new(t: TokenType, lexeme: impl Into<Lexeme>, span: Span) -> Self {
         Token {
             t,
-            literal: TokenLiteral::Str(literal),
-            line,
-            lexeme: lexeme.to_string(),
+            literal: None,
+            span,
+            lexeme: lexeme.into(),
+            extras: None,
         }
     }
 
-    pub fn with_value(t: TokenType, literal: TokenLiteral, lexeme: &str, line: usize) -> Self {
+    pub fn with_value(
+        t: TokenType,
+        literal: TokenLiteral,
+        lexeme: impl Into<Lexeme>,
+        span: Span,
+    ) -> Self {
         Token {
-            t,
-            literal,
-            line,
-            lexeme: lexeme.to_string(),
+            literal: Some(Rc::new(literal)),
+            ..Token::new(t, lexeme, span)
         }
     }
 
@@ -131,21 +253,88 @@ impl Token {
         &self.t
     }
 
+    // Shorthands for tests and tooling, the interpreter goes through `span`.
+    #[allow(dead_code)]
     pub fn line(&self) -> usize {
-        self.line
+        self.span.line
+    }
+
+    #[allow(dead_code)]
+    pub fn column(&self) -> usize {
+        self.span.column
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
     }
 
-    pub fn literal(&self) -> &TokenLiteral {
-        &self.literal
+    pub fn literal(&self) -> Option<&TokenLiteral> {
+        self.literal.as_deref()
     }
 
     pub fn lexeme(&self) -> &str {
-        &self.lexeme
+        self.lexeme.as_str()
+    }
+
+    // `///` comment lines directly preceding this token, joined by newlines.
+    pub fn doc(&self) -> Option<&str> {
+        self.extras.as_ref()?.doc.as_deref()
+    }
+
+    pub fn set_doc(&mut self, doc: String) {
+        self.extras_mut().doc = Some(doc);
+    }
+
+    // Only filled in by a lossless scanner. Trailing trivia runs up to the end
+    // of the token's line, everything after that leads the next token. Only
+    // tooling reads trivia.
+    #[allow(dead_code)]
+    pub fn leading_trivia(&self) -> &[Trivia] {
+        self.extras
+            .as_ref()
+            .map_or(&[], |extras| &extras.leading_trivia)
+    }
+
+    #[allow(dead_code)]
+    pub fn trailing_trivia(&self) -> &[Trivia] {
+        self.extras
+            .as_ref()
+            .map_or(&[], |extras| &extras.trailing_trivia)
+    }
+
+    pub fn set_leading_trivia(&mut self, trivia: Vec<Trivia>) {
+        if !trivia.is_empty() {
+            self.extras_mut().leading_trivia = trivia;
+        }
+    }
+
+    pub fn set_trailing_trivia(&mut self, trivia: Vec<Trivia>) {
+        if !trivia.is_empty() {
+            self.extras_mut().trailing_trivia = trivia;
+        }
+    }
+
+    // The token's source text together with its trivia.
+    #[allow(dead_code)]
+    pub fn full_text(&self) -> String {
+        let mut text = String::new();
+        for trivia in self.leading_trivia() {
+            text.push_str(trivia.text.as_str());
+        }
+        text.push_str(self.lexeme());
+        for trivia in self.trailing_trivia() {
+            text.push_str(trivia.text.as_str());
+        }
+        text
+    }
+
+    fn extras_mut(&mut self) -> &mut TokenExtras {
+        Rc::make_mut(self.extras.get_or_insert_with(Rc::default))
     }
 }
 
 impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?} {:?} {}", self.t, self.literal, self.lexeme)
+        write!(f, "{:?} {:?} {}", self.t, self.literal, self.lexeme())
     }
 }